@@ -2,11 +2,18 @@ use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
 
 use crate::{
-    resources::{BoardOptions, UNOPENED_INDEX},
-    GameResetEvent, CAMERA_SCALE, WINDOW_PADDING, WINDOW_TOP_HEIGHT,
+    components::WrapEdge,
+    engine::{points_up, Shape},
+    resources::{BoardOptions, Settings, UNOPENED_INDEX},
+    GameResetEvent, WINDOW_MIN_WIDTH, WINDOW_PADDING, WINDOW_TOP_HEIGHT,
 };
 
 pub const TILE_SIZE: f32 = 16.;
+pub const HEX_TILE_HEIGHT: f32 = 18.;
+pub const TRIANGLE_TILE_HEIGHT: f32 = 14.;
+const WRAP_EDGE_WIDTH: f32 = 2.;
+const WRAP_X_COLOR: Color = Color::rgb(0.2, 0.4, 1.0);
+const WRAP_Y_COLOR: Color = Color::rgb(1.0, 0.5, 0.1);
 
 pub fn board_setup(
     mut commands: Commands,
@@ -19,17 +26,102 @@ pub fn board_setup(
         commands.entity(tilemap_entity).despawn();
     }
 
-    let texture_handle = asset_server.load::<Image>("texture.png");
+    spawn_tilemap(&mut commands, &asset_server, &board_options);
+
+    board_reset_event.send(GameResetEvent(board_options.clone()));
+}
+
+/// Tile size, grid size, map type and atlas for each board shape.
+pub fn tilemap_layout(
+    shape: Shape,
+) -> (TilemapTileSize, TilemapGridSize, TilemapType, &'static str) {
+    match shape {
+        Shape::Square => (
+            TilemapTileSize::new(TILE_SIZE, TILE_SIZE),
+            TilemapGridSize::new(TILE_SIZE, TILE_SIZE),
+            TilemapType::Square,
+            "texture.png",
+        ),
+        Shape::Hex => (
+            TilemapTileSize::new(TILE_SIZE, HEX_TILE_HEIGHT),
+            TilemapGridSize::new(TILE_SIZE, HEX_TILE_HEIGHT),
+            TilemapType::Hexagon(HexCoordSystem::Row),
+            "hex.png",
+        ),
+        // Neighbouring triangles overlap by half a tile, one pointing up and the next down.
+        Shape::Triangle => (
+            TilemapTileSize::new(TILE_SIZE, TRIANGLE_TILE_HEIGHT),
+            TilemapGridSize::new(TILE_SIZE / 2., TRIANGLE_TILE_HEIGHT),
+            TilemapType::Square,
+            "tri.png",
+        ),
+    }
+}
+
+/// Finds the triangle under `pos`, given in tilemap space.
+pub fn triangle_at(pos: Vec2, map_size: &TilemapSize) -> Option<TilePos> {
+    let (half_width, height) = (TILE_SIZE / 2., TRIANGLE_TILE_HEIGHT);
+    let y = (pos.y / height + 0.5).floor();
+    let column = (pos.x / half_width + 0.5).floor();
+    if y < 0. || y >= map_size.y as f32 {
+        return None;
+    }
+    let dy = pos.y - y * height;
+
+    [column - 1., column, column + 1.]
+        .into_iter()
+        .filter(|x| *x >= 0. && *x < map_size.x as f32)
+        .map(|x| TilePos::new(x as u32, y as u32))
+        .find(|tile_pos| {
+            let dx = pos.x - tile_pos.x as f32 * half_width;
+            // Distance from the apex as a fraction of the height.
+            let depth = if points_up((tile_pos.x, tile_pos.y)) {
+                (height / 2. - dy) / height
+            } else {
+                (height / 2. + dy) / height
+            };
+            dx.abs() <= half_width * depth
+        })
+}
+
+pub fn spawn_tilemap(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    board_options: &BoardOptions,
+) -> Entity {
+    let (tile_size, grid_size, map_type, texture) = tilemap_layout(board_options.shape);
+    let texture_handle = asset_server.load::<Image>(texture);
 
     let map_size = TilemapSize::new(board_options.width, board_options.height);
 
     let tilemap_entity = commands.spawn_empty().id();
 
+    let tile_storage = spawn_tiles(commands, tilemap_entity, map_size);
+
+    commands.entity(tilemap_entity).insert(TilemapBundle {
+        grid_size,
+        map_type,
+        size: map_size,
+        storage: tile_storage,
+        texture: TilemapTexture::Single(texture_handle),
+        tile_size,
+        transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 0.0),
+        ..default()
+    });
+    tilemap_entity
+}
+
+/// Spawns one unopened tile for every `(x, y)` of a `width` by `height` map.
+pub fn spawn_tiles(
+    commands: &mut Commands,
+    tilemap_entity: Entity,
+    map_size: TilemapSize,
+) -> TileStorage {
     let mut tile_storage = TileStorage::empty(map_size);
 
-    for i in 0..map_size.x {
-        for j in 0..map_size.y {
-            let tile_pos = TilePos::new(i, j);
+    for x in 0..map_size.x {
+        for y in 0..map_size.y {
+            let tile_pos = TilePos::new(x, y);
             let tile_entity = commands
                 .spawn(TileBundle {
                     position: tile_pos,
@@ -41,46 +133,137 @@ pub fn board_setup(
             tile_storage.set(&tile_pos, tile_entity)
         }
     }
-
-    let tile_size = TilemapTileSize::new(TILE_SIZE, TILE_SIZE);
-    let grid_size = tile_size.into();
-    let map_type = TilemapType::default();
-
-    commands.entity(tilemap_entity).insert(TilemapBundle {
-        grid_size,
-        map_type,
-        size: map_size,
-        storage: tile_storage,
-        texture: TilemapTexture::Single(texture_handle),
-        tile_size,
-        transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, 0.0),
-        ..default()
-    });
-
-    board_reset_event.send(GameResetEvent(board_options.clone()));
+    tile_storage
 }
 
 pub fn board_resize(
-    mut tilemap_query: Query<&mut Transform, With<TilemapType>>,
+    mut tilemap_query: Query<(&mut Transform, &TilemapSize), With<TilemapType>>,
+    added_query: Query<(), Added<TilemapType>>,
     mut window_query: Query<&mut Window>,
     board_options: Res<BoardOptions>,
+    settings: Res<Settings>,
 ) {
-    let board_size =
-        Vec2::splat(TILE_SIZE) * Vec2::new(board_options.width as f32, board_options.height as f32);
+    if !board_options.is_changed() && !settings.is_changed() && added_query.is_empty() {
+        return;
+    }
+
+    // The atlases are drawn at `TILE_SIZE`, so other sizes scale the whole tilemap.
+    let tile_scale = settings.tile_size / TILE_SIZE;
+    let camera_scale = settings.camera_scale;
+    let (tile_size, ..) = tilemap_layout(board_options.shape);
+    let (min, max) = tile_center_bounds(&board_options);
+    let board_size = (max - min + Vec2::new(tile_size.x, tile_size.y)) * tile_scale;
 
     // info!("board_size:{:?}", board_size);
 
     if let Ok(mut window) = window_query.get_single_mut() {
-        let window_width = board_size.x * CAMERA_SCALE + WINDOW_PADDING + WINDOW_PADDING;
-        let window_height = board_size.y * CAMERA_SCALE + WINDOW_TOP_HEIGHT + WINDOW_PADDING;
+        let window_width =
+            (board_size.x * camera_scale + WINDOW_PADDING + WINDOW_PADDING).max(WINDOW_MIN_WIDTH);
+        let window_height = board_size.y * camera_scale + WINDOW_TOP_HEIGHT + WINDOW_PADDING;
 
         // info!("WindowSize: {}x{}", window_width, window_height);
         window.resolution.set(window_width, window_height);
         // window.position.center(MonitorSelection::Current);
 
-        if let Ok(mut transform) = tilemap_query.get_single_mut() {
-            transform.translation.x = -(window_width / CAMERA_SCALE) / 2.0 + WINDOW_PADDING;
-            transform.translation.y = -(window_height / CAMERA_SCALE) / 2.0 + WINDOW_PADDING;
+        for (mut transform, map_size) in tilemap_query.iter_mut() {
+            if map_size.x != board_options.width || map_size.y != board_options.height {
+                continue;
+            }
+            transform.scale = Vec3::new(tile_scale, tile_scale, 1.0);
+            transform.translation.x = -(min.x + max.x) / 2.0 * tile_scale;
+            // The bottom edge of the board sits the padding above the bottom of the window.
+            transform.translation.y = -(window_height / camera_scale) / 2.0
+                + WINDOW_PADDING / camera_scale
+                + (tile_size.y / 2.0 - min.y) * tile_scale;
         }
     };
 }
+
+/// The lowest and highest tile centers of the board in tilemap space.
+pub fn tile_center_bounds(board_options: &BoardOptions) -> (Vec2, Vec2) {
+    // Tile centers are linear in the tile position, so the corners bound the whole map.
+    let (_, grid_size, map_type, _) = tilemap_layout(board_options.shape);
+    let (max_x, max_y) = (
+        board_options.width.saturating_sub(1),
+        board_options.height.saturating_sub(1),
+    );
+    let corners = [(0, 0), (max_x, 0), (0, max_y), (max_x, max_y)]
+        .map(|(x, y)| TilePos::new(x, y).center_in_world(&grid_size, &map_type));
+    let min = corners
+        .iter()
+        .copied()
+        .reduce(Vec2::min)
+        .unwrap_or_default();
+    let max = corners
+        .iter()
+        .copied()
+        .reduce(Vec2::max)
+        .unwrap_or_default();
+    (min, max)
+}
+
+/// Draws matching bars on opposite edges of a wrapping board.
+pub fn wrap_edge_system(
+    mut commands: Commands,
+    edge_query: Query<Entity, With<WrapEdge>>,
+    tilemap_query: Query<(
+        Entity,
+        &TilemapSize,
+        &TilemapTileSize,
+        &TilemapGridSize,
+        &TilemapType,
+    )>,
+    added_query: Query<(), Added<TilemapType>>,
+    board_options: Res<BoardOptions>,
+) {
+    if !board_options.is_changed() && added_query.is_empty() {
+        return;
+    }
+    for edge in edge_query.iter() {
+        commands.entity(edge).despawn_recursive();
+    }
+    if !board_options.wraps() {
+        return;
+    }
+
+    for (tilemap_entity, map_size, tile_size, grid_size, map_type) in tilemap_query.iter() {
+        let mut bars = Vec::new();
+        for y in 0..map_size.y {
+            for (x, side) in [(0, -1.0), (map_size.x - 1, 1.0)] {
+                let center = TilePos::new(x, y).center_in_world(grid_size, map_type);
+                bars.push((
+                    center + Vec2::new(side * tile_size.x / 2.0, 0.0),
+                    Vec2::new(WRAP_EDGE_WIDTH, grid_size.y),
+                    WRAP_X_COLOR,
+                ));
+            }
+        }
+        for x in 0..map_size.x {
+            for (y, side) in [(0, -1.0), (map_size.y - 1, 1.0)] {
+                let center = TilePos::new(x, y).center_in_world(grid_size, map_type);
+                bars.push((
+                    center + Vec2::new(0.0, side * tile_size.y / 2.0),
+                    Vec2::new(grid_size.x, WRAP_EDGE_WIDTH),
+                    WRAP_Y_COLOR,
+                ));
+            }
+        }
+
+        commands.entity(tilemap_entity).with_children(|children| {
+            for (position, size, color) in bars {
+                children.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color,
+                            custom_size: Some(size),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(position.extend(1.0)),
+                        ..default()
+                    },
+                    WrapEdge,
+                ));
+            }
+        });
+    }
+}
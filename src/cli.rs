@@ -0,0 +1,283 @@
+use std::{fmt, path::PathBuf, process};
+
+use crate::{
+    components::Level,
+    engine::{BoardOptions, FirstClick, OptionsError},
+    save::{read_game_file, SaveFile},
+};
+
+pub const USAGE: &str = "\
+Usage: minesweeper [OPTIONS]
+
+Options:
+  -l, --level <LEVEL>          base, pro or expert [default: from the settings]
+  -W, --width <N>              board width, instead of the level's
+  -H, --height <N>             board height, instead of the level's
+  -m, --mines <N>              mine count, instead of the level's
+  -s, --seed <N>               fixed seed for the first board
+  -f, --first-click <POLICY>   opening, safe or any [default: from the settings]
+      --load <FILE>            start from a saved game (.ron), a text board
+                               (.txt) or an MBF board (.mbf)
+  -h, --help                   print this help
+";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Play(Args),
+    Help,
+}
+
+/// What the first game starts with.
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub options: BoardOptions,
+    pub load: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ArgsError {
+    Unknown(String),
+    MissingValue(String),
+    InvalidValue {
+        flag: String,
+        value: String,
+        expected: &'static str,
+    },
+    /// A loaded game brings its own board.
+    LoadWithBoard,
+    Options(OptionsError),
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgsError::Unknown(arg) => write!(f, "unknown argument '{}'", arg),
+            ArgsError::MissingValue(flag) => write!(f, "{} needs a value", flag),
+            ArgsError::InvalidValue {
+                flag,
+                value,
+                expected,
+            } => write!(f, "'{}' for {} is not {}", value, flag, expected),
+            ArgsError::LoadWithBoard => write!(f, "--load can't be combined with board options"),
+            ArgsError::Options(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<OptionsError> for ArgsError {
+    fn from(error: OptionsError) -> Self {
+        ArgsError::Options(error)
+    }
+}
+
+fn invalid(flag: &str, value: &str, expected: &'static str) -> ArgsError {
+    ArgsError::InvalidValue {
+        flag: flag.to_string(),
+        value: value.to_string(),
+        expected,
+    }
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, ArgsError> {
+    value.parse().map_err(|_| invalid(flag, value, "a number"))
+}
+
+/// Parses the arguments after the program name, starting from `defaults`.
+/// Values follow their flag either as the next argument or after `=`.
+pub fn parse(
+    args: impl IntoIterator<Item = String>,
+    defaults: BoardOptions,
+) -> Result<Command, ArgsError> {
+    let mut size = (defaults.width, defaults.height, defaults.bomb_count);
+    let (mut width, mut height, mut mines) = (None, None, None);
+    let mut seed = None;
+    let mut first_click = defaults.first_click;
+    let mut load = None;
+    let mut board_flags = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
+            }
+            _ => (arg, None),
+        };
+        if flag == "-h" || flag == "--help" {
+            return Ok(Command::Help);
+        }
+        if !matches!(
+            flag.as_str(),
+            "-l" | "--level"
+                | "-W"
+                | "--width"
+                | "-H"
+                | "--height"
+                | "-m"
+                | "--mines"
+                | "-s"
+                | "--seed"
+                | "-f"
+                | "--first-click"
+                | "--load"
+        ) {
+            return Err(ArgsError::Unknown(flag));
+        }
+
+        let value = inline
+            .or_else(|| args.next())
+            .ok_or_else(|| ArgsError::MissingValue(flag.clone()))?;
+        match flag.as_str() {
+            "-l" | "--level" => {
+                size = Level::ALL
+                    .iter()
+                    .find(|level| level.name().eq_ignore_ascii_case(&value))
+                    .ok_or_else(|| invalid(&flag, &value, "base, pro or expert"))?
+                    .size();
+            }
+            "-W" | "--width" => width = Some(number(&flag, &value)?),
+            "-H" | "--height" => height = Some(number(&flag, &value)?),
+            "-m" | "--mines" => mines = Some(number(&flag, &value)?),
+            "-s" | "--seed" => seed = Some(number(&flag, &value)?),
+            "-f" | "--first-click" => {
+                first_click = match value.to_ascii_lowercase().as_str() {
+                    "opening" => FirstClick::Opening,
+                    "safe" => FirstClick::Safe,
+                    "any" | "unprotected" => FirstClick::Unprotected,
+                    _ => return Err(invalid(&flag, &value, "opening, safe or any")),
+                };
+            }
+            _ => {
+                load = Some(PathBuf::from(value));
+                continue;
+            }
+        }
+        board_flags = true;
+    }
+
+    if load.is_some() && board_flags {
+        return Err(ArgsError::LoadWithBoard);
+    }
+    let options = BoardOptions {
+        width: width.unwrap_or(size.0),
+        height: height.unwrap_or(size.1),
+        bomb_count: mines.unwrap_or(size.2),
+        seed,
+        first_click,
+        ..defaults
+    };
+    options.validate()?;
+    Ok(Command::Play(Args { options, load }))
+}
+
+/// The options and game to start with, printing the help or an error and
+/// exiting when there is nothing to play.
+pub fn from_env(defaults: BoardOptions) -> (BoardOptions, Option<SaveFile>) {
+    let args = match parse(std::env::args().skip(1), defaults) {
+        Ok(Command::Play(args)) => args,
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            process::exit(0);
+        }
+        Err(error) => {
+            eprintln!("error: {}\nRun with --help to see the options.", error);
+            process::exit(2);
+        }
+    };
+
+    let Some(path) = args.load else {
+        return (args.options, None);
+    };
+    match read_game_file(&path) {
+        Ok(save) => (save.board.options.clone(), Some(save)),
+        Err(error) => {
+            eprintln!("error: could not load {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &str) -> Result<Command, ArgsError> {
+        parse(
+            args.split_whitespace().map(String::from),
+            BoardOptions::default(),
+        )
+    }
+
+    fn options(args: &str) -> BoardOptions {
+        match parse_str(args) {
+            Ok(Command::Play(args)) => args.options,
+            other => panic!("not a game: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn levels_and_sizes() {
+        assert_eq!(options(""), BoardOptions::default());
+        assert_eq!(options("--level Expert"), BoardOptions::new(30, 16, 99));
+        assert_eq!(options("-l pro -m 50"), BoardOptions::new(16, 16, 50));
+        assert_eq!(
+            options("--width=20 -H 10 --mines=30 -s 7 -f safe"),
+            BoardOptions {
+                seed: Some(7),
+                first_click: FirstClick::Safe,
+                ..BoardOptions::new(20, 10, 30)
+            }
+        );
+        assert_eq!(parse_str("-s 1 --help"), Ok(Command::Help));
+
+        let defaults = BoardOptions {
+            first_click: FirstClick::Safe,
+            question_marks: true,
+            ..BoardOptions::new(16, 16, 40)
+        };
+        assert_eq!(
+            parse(["-m".to_string(), "30".to_string()], defaults.clone()),
+            Ok(Command::Play(Args {
+                options: BoardOptions {
+                    bomb_count: 30,
+                    ..defaults
+                },
+                load: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse_str("--size 3"),
+            Err(ArgsError::Unknown("--size".to_string()))
+        );
+        assert_eq!(
+            parse_str("--mines"),
+            Err(ArgsError::MissingValue("--mines".to_string()))
+        );
+        assert_eq!(
+            parse_str("-f first").unwrap_err().to_string(),
+            "'first' for -f is not opening, safe or any"
+        );
+        assert_eq!(
+            parse_str("-W 3 -H 3 -m 9"),
+            Err(ArgsError::Options(OptionsError::TooManyMines {
+                bomb_count: 9,
+                most: 8
+            }))
+        );
+        assert_eq!(
+            parse_str("--load board.txt -l pro"),
+            Err(ArgsError::LoadWithBoard)
+        );
+        assert_eq!(
+            parse_str("--load board.txt"),
+            Ok(Command::Play(Args {
+                options: BoardOptions::default(),
+                load: Some(PathBuf::from("board.txt")),
+            }))
+        );
+    }
+}
@@ -15,6 +15,115 @@ pub struct MainButton;
 #[derive(Component)]
 pub struct LevelButton;
 
+#[derive(Component)]
+pub struct SeedButton;
+
+#[derive(Component)]
+pub struct SeedText;
+
+#[derive(Component)]
+pub struct FirstClickButton;
+
+#[derive(Component)]
+pub struct FirstClickText;
+
+#[derive(Component)]
+pub struct NoGuessButton;
+
+#[derive(Component)]
+pub struct NoGuessText;
+
+#[derive(Component)]
+pub struct ShapeButton;
+
+#[derive(Component)]
+pub struct ShapeText;
+
+#[derive(Component)]
+pub struct WrapButton;
+
+#[derive(Component)]
+pub struct WrapText;
+
+#[derive(Component)]
+pub struct WrapEdge;
+
+#[derive(Component)]
+pub struct NeighbourhoodButton;
+
+#[derive(Component)]
+pub struct NeighbourhoodText;
+
+#[derive(Component)]
+pub struct MultiMineButton;
+
+#[derive(Component)]
+pub struct MultiMineText;
+
+#[derive(Component)]
+pub struct AntiMineButton;
+
+#[derive(Component)]
+pub struct AntiMineText;
+
+#[derive(Component)]
+pub struct QuestionButton;
+
+#[derive(Component)]
+pub struct QuestionText;
+
+#[derive(Component)]
+pub struct HintButton;
+
+#[derive(Component)]
+pub struct MessageText;
+
+/// Text drawn on the window background, so it follows the theme.
+#[derive(Component)]
+pub struct ThemedText;
+
+#[derive(Component)]
+pub struct ProbabilityLabel;
+
+/// A count beyond the atlas, written over its tile.
+#[derive(Component)]
+pub struct CountLabel;
+
+#[derive(Component)]
+pub struct MetricsPanel;
+
+#[derive(Component)]
+pub struct StatsButton;
+
+#[derive(Component)]
+pub struct CustomButton;
+
+#[derive(Component)]
+pub struct CustomDialogPanel;
+
+/// A field of the custom board dialog, holding its index.
+#[derive(Component)]
+pub struct CustomField(pub usize);
+
+#[derive(Component)]
+pub enum CustomAction {
+    Start,
+    Cancel,
+}
+
+#[derive(Component)]
+pub struct StatsPanel;
+
+#[derive(Component)]
+pub struct LeaderboardButton;
+
+#[derive(Component)]
+pub struct LeaderboardPanel;
+
+/// A tab on the leaderboard, holding its index into `Level::ALL`.
+#[derive(Component)]
+pub struct LeaderboardTab(pub usize);
+
 #[derive(Component, Debug)]
 pub enum NumberIndex {
     One,
@@ -22,9 +131,30 @@ pub enum NumberIndex {
     Threr,
 }
 
-#[derive(Component)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Level {
     Base,
     Pro,
     Expert,
 }
+
+impl Level {
+    pub const ALL: [Level; 3] = [Level::Base, Level::Pro, Level::Expert];
+
+    /// Width, height and mine count.
+    pub fn size(&self) -> (u32, u32, u32) {
+        match self {
+            Level::Base => (9, 9, 10),
+            Level::Pro => (16, 16, 40),
+            Level::Expert => (30, 16, 99),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Base => "Base",
+            Level::Pro => "Pro",
+            Level::Expert => "Expert",
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{CustomAction, CustomDialogPanel, CustomField},
+    engine::OptionsError,
+    panel::{panel_text, spawn_panel},
+    resources::{BoardOptions, CustomDialog},
+    GameResetEvent,
+};
+
+pub const MIN_CUSTOM_SIZE: u32 = 2;
+pub const MAX_CUSTOM_SIZE: u32 = 100;
+/// Denser boards are mostly guessing.
+pub const MAX_DENSITY_PERCENT: u32 = 90;
+
+const FIELD_NAMES: [&str; 3] = ["Width", "Height", "Mines"];
+const MAX_FIELD_LENGTH: usize = 6;
+
+fn parse_size(name: &str, text: &str) -> Result<u32, String> {
+    let size = text
+        .parse::<u32>()
+        .map_err(|_| format!("{} must be a whole number", name))?;
+    if !(MIN_CUSTOM_SIZE..=MAX_CUSTOM_SIZE).contains(&size) {
+        return Err(format!(
+            "{} must be between {} and {}",
+            name, MIN_CUSTOM_SIZE, MAX_CUSTOM_SIZE
+        ));
+    }
+    Ok(size)
+}
+
+/// Width, height and mine count from the dialog fields. The mines can be a
+/// count or a density such as `15%`.
+pub fn parse_custom(width: &str, height: &str, mines: &str) -> Result<BoardOptions, String> {
+    let width = parse_size("Width", width.trim())?;
+    let height = parse_size("Height", height.trim())?;
+    let area = width * height;
+
+    let mines = mines.trim();
+    let bomb_count = if let Some(percent) = mines.strip_suffix('%') {
+        let percent = percent
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| "Density must be a whole percentage".to_string())?;
+        if !(1..=MAX_DENSITY_PERCENT).contains(&percent) {
+            return Err(format!(
+                "Density must be between 1% and {}%",
+                MAX_DENSITY_PERCENT
+            ));
+        }
+        // Rounded to the nearest mine, but never down to none.
+        ((area * percent + 50) / 100).max(1)
+    } else {
+        mines
+            .parse::<u32>()
+            .map_err(|_| "Mines must be a number or a percentage".to_string())?
+    };
+
+    let most = area * MAX_DENSITY_PERCENT / 100;
+    if bomb_count == 0 || bomb_count > most {
+        return Err(format!(
+            "Mines must be between 1 and {} on a {}x{} board",
+            most, width, height
+        ));
+    }
+    BoardOptions::try_new(width, height, bomb_count).map_err(playable_error)
+}
+
+fn submit(
+    dialog: &mut CustomDialog,
+    board_options: &BoardOptions,
+    game_reset_event: &mut EventWriter<GameResetEvent>,
+) {
+    let options =
+        parse_custom(&dialog.fields[0], &dialog.fields[1], &dialog.fields[2]).and_then(|custom| {
+            // The other options stay as they are, and anti-mines still have to fit.
+            let options = BoardOptions {
+                width: custom.width,
+                height: custom.height,
+                bomb_count: custom.bomb_count,
+                seed: None,
+                ..board_options.clone()
+            };
+            options.validate().map_err(playable_error)?;
+            Ok(options)
+        });
+    match options {
+        Ok(options) => {
+            game_reset_event.send(GameResetEvent(options));
+            dialog.open = false;
+        }
+        Err(error) => dialog.error = Some(error),
+    }
+}
+
+fn playable_error(error: OptionsError) -> String {
+    format!("Not a playable board: {}", error)
+}
+
+/// Digits go into the focused field (and `%` into the mines), Tab moves on,
+/// Enter starts the board and Escape closes the dialog.
+pub fn custom_input_system(
+    mut char_events: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut dialog: ResMut<CustomDialog>,
+    board_options: Res<BoardOptions>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+) {
+    if !dialog.open {
+        char_events.clear();
+        return;
+    }
+
+    for event in char_events.read() {
+        let field = dialog.field;
+        let accepted = event.char.is_ascii_digit() || (event.char == '%' && field == 2);
+        if accepted && dialog.fields[field].len() < MAX_FIELD_LENGTH {
+            dialog.fields[field].push(event.char);
+            dialog.error = None;
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        let field = dialog.field;
+        dialog.fields[field].pop();
+    }
+    if keys.just_pressed(KeyCode::Tab) {
+        dialog.field = (dialog.field + 1) % FIELD_NAMES.len();
+    }
+
+    if keys.just_pressed(KeyCode::Escape) {
+        dialog.open = false;
+    } else if keys.just_pressed(KeyCode::Return) {
+        submit(&mut dialog, &board_options, &mut game_reset_event);
+    }
+}
+
+pub fn custom_click_system(
+    field_query: Query<(&Interaction, &CustomField), Changed<Interaction>>,
+    action_query: Query<(&Interaction, &CustomAction), Changed<Interaction>>,
+    mut dialog: ResMut<CustomDialog>,
+    board_options: Res<BoardOptions>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+) {
+    for (interaction, field) in field_query.iter() {
+        if interaction == &Interaction::Pressed {
+            dialog.field = field.0;
+        }
+    }
+    for (interaction, action) in action_query.iter() {
+        if interaction == &Interaction::Pressed {
+            match action {
+                CustomAction::Start => submit(&mut dialog, &board_options, &mut game_reset_event),
+                CustomAction::Cancel => dialog.open = false,
+            }
+        }
+    }
+}
+
+pub fn custom_dialog_system(
+    mut commands: Commands,
+    panel_query: Query<Entity, With<CustomDialogPanel>>,
+    dialog: Res<CustomDialog>,
+) {
+    if !dialog.is_changed() {
+        return;
+    }
+
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !dialog.open {
+        return;
+    }
+
+    spawn_panel(&mut commands, CustomDialogPanel, |children| {
+        children.spawn(panel_text("Custom board", Color::BLACK));
+        for (index, name) in FIELD_NAMES.iter().enumerate() {
+            let (cursor, color) = if index == dialog.field {
+                ("_", Color::BLACK)
+            } else {
+                ("", Color::GRAY)
+            };
+            children.spawn((
+                panel_text(
+                    format!("{}: {}{}", name, dialog.fields[index], cursor),
+                    color,
+                ),
+                Interaction::default(),
+                CustomField(index),
+            ));
+        }
+        children.spawn(panel_text(
+            "Mines can also be a density, such as 15%",
+            Color::GRAY,
+        ));
+        if let Some(error) = &dialog.error {
+            children.spawn(panel_text(error.clone(), Color::RED));
+        }
+        children
+            .spawn(NodeBundle {
+                style: Style {
+                    column_gap: Val::Px(15.0),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|actions| {
+                actions.spawn((
+                    panel_text("Start", Color::BLACK),
+                    Interaction::default(),
+                    CustomAction::Start,
+                ));
+                actions.spawn((
+                    panel_text("Cancel", Color::BLACK),
+                    Interaction::default(),
+                    CustomAction::Cancel,
+                ));
+            });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(width: &str, height: &str, mines: &str) -> Result<(u32, u32, u32), String> {
+        parse_custom(width, height, mines)
+            .map(|options| (options.width, options.height, options.bomb_count))
+    }
+
+    #[test]
+    fn sizes_stay_in_bounds() {
+        assert_eq!(size("2", "2", "1"), Ok((2, 2, 1)));
+        assert_eq!(size("100", "100", "10"), Ok((100, 100, 10)));
+        assert_eq!(size(" 30 ", "16", "99"), Ok((30, 16, 99)));
+        assert_eq!(
+            size("1", "9", "1"),
+            Err("Width must be between 2 and 100".to_string())
+        );
+        assert_eq!(
+            size("9", "101", "1"),
+            Err("Height must be between 2 and 100".to_string())
+        );
+        assert_eq!(
+            size("", "9", "1"),
+            Err("Width must be a whole number".to_string())
+        );
+    }
+
+    #[test]
+    fn density_stays_in_bounds() {
+        assert_eq!(size("10", "10", "1%"), Ok((10, 10, 1)));
+        assert_eq!(size("10", "10", "90%"), Ok((10, 10, 90)));
+        assert_eq!(
+            size("10", "10", "0%"),
+            Err("Density must be between 1% and 90%".to_string())
+        );
+        assert_eq!(
+            size("10", "10", "91%"),
+            Err("Density must be between 1% and 90%".to_string())
+        );
+        assert_eq!(
+            size("10", "10", "1.5%"),
+            Err("Density must be a whole percentage".to_string())
+        );
+    }
+
+    #[test]
+    fn count_and_density_share_the_limit() {
+        // Densities round to the nearest mine but never to none.
+        assert_eq!(size("3", "3", "15%"), Ok((3, 3, 1)));
+        assert_eq!(size("2", "2", "1%"), Ok((2, 2, 1)));
+        assert_eq!(size("16", "16", "15%"), Ok((16, 16, 38)));
+        // A count may go as high as the densest board and no higher.
+        assert_eq!(size("10", "10", "90"), Ok((10, 10, 90)));
+        assert_eq!(
+            size("10", "10", "91"),
+            Err("Mines must be between 1 and 90 on a 10x10 board".to_string())
+        );
+        assert_eq!(
+            size("10", "10", "0"),
+            Err("Mines must be between 1 and 90 on a 10x10 board".to_string())
+        );
+        assert_eq!(
+            size("10", "10", "many"),
+            Err("Mines must be a number or a percentage".to_string())
+        );
+    }
+}
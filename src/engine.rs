@@ -0,0 +1,1292 @@
+use std::fmt;
+
+use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::solver;
+
+/// How many layouts a no-guess board tries before falling back to a plain one.
+const NO_GUESS_ATTEMPTS: u64 = 10_000;
+
+/// Solving a layout takes time roughly square in its area, so the attempts are
+/// limited to this many squared cells and the biggest boards aren't searched.
+const NO_GUESS_WORK: u64 = 50_000_000;
+
+/// Denser boards almost never come out guess-free, so they aren't searched at all.
+const NO_GUESS_MAX_DENSITY: f32 = 0.22;
+
+/// The most mines a single cell can hold when `multi_mine` is on.
+pub const MAX_MINES_PER_CELL: u32 = 3;
+
+/// The most cells a board can have. Bigger boards take too long to generate and draw.
+pub const MAX_AREA: u32 = 1 << 20;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Serialize, Deserialize)]
+// Options added later fall back to their defaults when older files lack them.
+#[serde(default)]
+pub struct BoardOptions {
+    pub width: u32,
+    pub height: u32,
+    pub bomb_count: u32,
+    /// Fixed seed for the mine layout, a random one is picked when `None`.
+    pub seed: Option<u64>,
+    pub first_click: FirstClick,
+    /// Only accept layouts that can be solved without guessing from the first click.
+    pub no_guess: bool,
+    pub shape: Shape,
+    /// Opposite edges are neighbours, so the board is a torus.
+    pub wrap: bool,
+    /// Which cells count as neighbours on a square board.
+    pub neighbourhood: Neighbourhood,
+    /// Cells hold up to `MAX_MINES_PER_CELL` mines and numbers count mines, not cells.
+    pub multi_mine: bool,
+    /// Anti-mines placed in random empty cells; each counts as -1 in the numbers.
+    pub anti_mine_count: u32,
+    /// Marking cycles through a "?" after the flags.
+    pub question_marks: bool,
+}
+
+impl Default for BoardOptions {
+    fn default() -> Self {
+        Self::new(9, 9, 10)
+    }
+}
+
+/// Why a set of `BoardOptions` can't make a board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionsError {
+    /// The width or the height is zero.
+    EmptyBoard,
+    /// More than `MAX_AREA` cells, including sizes that overflow `u32`.
+    TooLarge { width: u32, height: u32 },
+    /// At least one cell has to stay free of mines.
+    TooManyMines { bomb_count: u32, most: u32 },
+    /// Anti-mines only go in cells without mines.
+    TooManyAntiMines { anti_mine_count: u32, most: u32 },
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionsError::EmptyBoard => write!(f, "the board has no cells"),
+            OptionsError::TooLarge { width, height } => write!(
+                f,
+                "a {}x{} board is larger than {} cells",
+                width, height, MAX_AREA
+            ),
+            OptionsError::TooManyMines { bomb_count, most } => write!(
+                f,
+                "{} mines don't fit, the board takes at most {}",
+                bomb_count, most
+            ),
+            OptionsError::TooManyAntiMines {
+                anti_mine_count,
+                most,
+            } => write!(
+                f,
+                "{} anti-mines don't fit, the board takes at most {}",
+                anti_mine_count, most
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+impl BoardOptions {
+    /// Unchecked: `Board::reset` panics or hangs on options that `validate` rejects.
+    pub fn new(w: u32, h: u32, b: u32) -> Self {
+        BoardOptions {
+            width: w,
+            height: h,
+            bomb_count: b,
+            seed: None,
+            first_click: FirstClick::default(),
+            no_guess: false,
+            shape: Shape::default(),
+            wrap: false,
+            neighbourhood: Neighbourhood::default(),
+            multi_mine: false,
+            anti_mine_count: 0,
+            question_marks: false,
+        }
+    }
+
+    /// Like `new`, but only for sizes and mine counts that make a board.
+    pub fn try_new(w: u32, h: u32, b: u32) -> Result<Self, OptionsError> {
+        let options = Self::new(w, h, b);
+        options.validate()?;
+        Ok(options)
+    }
+
+    pub fn validate(&self) -> Result<(), OptionsError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(OptionsError::EmptyBoard);
+        }
+        let area = self
+            .width
+            .checked_mul(self.height)
+            .filter(|area| *area <= MAX_AREA)
+            .ok_or(OptionsError::TooLarge {
+                width: self.width,
+                height: self.height,
+            })?;
+
+        let per_cell = if self.multi_mine {
+            MAX_MINES_PER_CELL
+        } else {
+            1
+        };
+        let most = (area - 1) * per_cell;
+        if self.bomb_count > most {
+            return Err(OptionsError::TooManyMines {
+                bomb_count: self.bomb_count,
+                most,
+            });
+        }
+
+        // Multiple mines may share a cell, but there is no counting on it.
+        let most = (area - 1).saturating_sub(self.bomb_count);
+        if self.anti_mine_count > most {
+            return Err(OptionsError::TooManyAntiMines {
+                anti_mine_count: self.anti_mine_count,
+                most,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn area(&self) -> u32 {
+        self.width * self.height
+    }
+
+    /// Numbers count mined cells, which is all the solver understands.
+    pub fn plain_numbers(&self) -> bool {
+        !self.multi_mine && self.anti_mine_count == 0
+    }
+
+    /// Triangles only tile a torus when both sides are even, otherwise the
+    /// orientation would not alternate across the seam.
+    pub fn wraps(&self) -> bool {
+        self.wrap
+            && (self.shape != Shape::Triangle
+                || (self.width.is_multiple_of(2) && self.height.is_multiple_of(2)))
+    }
+}
+
+/// How much the first uncovered tile is protected.
+#[derive(Debug, Default, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum FirstClick {
+    /// The board is left as generated.
+    Unprotected,
+    /// The first tile is never a mine.
+    Safe,
+    /// The first tile is always a zero, so it opens an area.
+    #[default]
+    Opening,
+}
+
+/// The tiling of the board.
+#[derive(Debug, Default, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Shape {
+    #[default]
+    Square,
+    /// Pointy-top hexagons in axial coordinates, so the board is a rhombus.
+    Hex,
+    /// Alternating triangles; `(x, y)` points up when `x + y` is even.
+    Triangle,
+}
+
+/// Neighbour sets for the square grid.
+#[derive(Debug, Default, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Neighbourhood {
+    /// The eight surrounding cells.
+    #[default]
+    Moore,
+    /// The eight cells a chess knight can reach.
+    Knight,
+    /// The four orthogonal cells.
+    Cross,
+    /// The sixteen cells on the edge of the 5x5 square around the cell.
+    Ring,
+}
+
+/// What the player sees on a tile.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tile {
+    #[default]
+    Unopened,
+    Marked,
+    /// Marked as holding an anti-mine.
+    AntiMarked,
+    /// A "?" reminder, which counts as unmarked everywhere.
+    Questioned,
+    Opened,
+}
+
+impl Tile {
+    /// Not opened and not flagged, so it can still be uncovered.
+    pub fn is_hidden(&self) -> bool {
+        matches!(self, Tile::Unopened | Tile::Questioned)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameStatus {
+    #[default]
+    Playing,
+    Won,
+    Lost,
+}
+
+/// The whole game: where the mines are (`map`, one entry per mine count,
+/// -1 for an anti-mine) and what the player has opened or marked so far (`tiles`).
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Board {
+    pub map: Vec<Vec<i32>>,
+    pub tiles: Vec<Vec<Tile>>,
+    /// How many flags sit on each marked tile, indexed like `map`.
+    pub flags: Vec<Vec<u32>>,
+    pub status: GameStatus,
+    pub exploded: Option<(u32, u32)>,
+    pub seed: u64,
+    pub options: BoardOptions,
+    pub started: bool,
+    /// Moves were undone during this game.
+    pub assisted: bool,
+    /// No guess-free layout was found, so the board was generated as usual.
+    #[serde(skip)]
+    pub no_guess_failed: bool,
+    /// Loaded already finished from a file, so it was counted when it was played.
+    #[serde(skip)]
+    pub restored: bool,
+}
+
+const SQUARE_COORD: [(i8, i8); 8] = [
+    // Top Left
+    (-1, 1),
+    // Top
+    (0, 1),
+    // Top right
+    (1, 1),
+    // Left
+    (-1, 0),
+    // Right
+    (1, 0),
+    // Bottom left
+    (-1, -1),
+    // Bottom
+    (0, -1),
+    // Bottom right
+    (1, -1),
+];
+
+const KNIGHT_COORD: [(i8, i8); 8] = [
+    (-2, 1),
+    (-1, 2),
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+];
+
+const CROSS_COORD: [(i8, i8); 4] = [(0, 1), (-1, 0), (1, 0), (0, -1)];
+
+const RING_COORD: [(i8, i8); 16] = [
+    (-2, 2),
+    (-1, 2),
+    (0, 2),
+    (1, 2),
+    (2, 2),
+    (-2, 1),
+    (2, 1),
+    (-2, 0),
+    (2, 0),
+    (-2, -1),
+    (2, -1),
+    (-2, -2),
+    (-1, -2),
+    (0, -2),
+    (1, -2),
+    (2, -2),
+];
+
+const HEX_COORD: [(i8, i8); 6] = [
+    // Top left
+    (-1, 1),
+    // Top right
+    (0, 1),
+    // Left
+    (-1, 0),
+    // Right
+    (1, 0),
+    // Bottom left
+    (0, -1),
+    // Bottom right
+    (1, -1),
+];
+
+// A triangle touches two cells on each side along its row, five cells across its
+// base and three around its apex.
+const TRIANGLE_UP_COORD: [(i8, i8); 12] = [
+    (-2, 0),
+    (-1, 0),
+    (1, 0),
+    (2, 0),
+    (-2, -1),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (2, -1),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+const TRIANGLE_DOWN_COORD: [(i8, i8); 12] = [
+    (-2, 0),
+    (-1, 0),
+    (1, 0),
+    (2, 0),
+    (-2, 1),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (2, 1),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// Whether the triangle at `coord` points up.
+pub fn points_up(coord: (u32, u32)) -> bool {
+    (coord.0 + coord.1).is_multiple_of(2)
+}
+
+impl Board {
+    pub fn reset(&mut self, options: &BoardOptions) {
+        let seed = options
+            .seed
+            .unwrap_or_else(|| thread_rng().gen::<u32>() as u64);
+        self.options = options.clone();
+        self.generate(seed);
+    }
+
+    fn generate(&mut self, seed: u64) {
+        let options = &self.options;
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut map = (0..options.area())
+            .map(|i| if i < options.bomb_count { 1 } else { 0 })
+            .collect::<Vec<i32>>();
+
+        if options.multi_mine {
+            // Every cell offers `MAX_MINES_PER_CELL` slots and the mines take random slots.
+            let mut slots = (0..options.area() * MAX_MINES_PER_CELL)
+                .map(|i| (i / MAX_MINES_PER_CELL) as usize)
+                .collect::<Vec<_>>();
+            slots.shuffle(&mut rng);
+            map.iter_mut().for_each(|bombs| *bombs = 0);
+            for cell in slots.into_iter().take(options.bomb_count as usize) {
+                map[cell] += 1;
+            }
+        } else {
+            for i in 0..options.area() {
+                let index = i as usize;
+                let random = rng.gen_range(0..options.area()) as usize;
+                map.swap(index, random);
+            }
+        }
+
+        let mut empty = (0..map.len()).filter(|i| map[*i] == 0).collect::<Vec<_>>();
+        empty.shuffle(&mut rng);
+        for cell in empty.into_iter().take(options.anti_mine_count as usize) {
+            map[cell] = -1;
+        }
+
+        // The map is stored column by column, so it is indexed as `map[x][y]`.
+        let height = options.height as usize;
+        self.map = (0..options.width as usize)
+            .map(|x| map[x * height..(x + 1) * height].to_vec())
+            .collect();
+        self.tiles = self
+            .map
+            .iter()
+            .map(|column| vec![Tile::Unopened; column.len()])
+            .collect();
+        self.flags = self
+            .map
+            .iter()
+            .map(|column| vec![0; column.len()])
+            .collect();
+        self.status = GameStatus::Playing;
+        self.exploded = None;
+        self.seed = seed;
+        self.started = false;
+        self.assisted = false;
+        self.no_guess_failed = false;
+        self.restored = false;
+    }
+
+    /// Replaces the mines with a recorded layout from before the first click,
+    /// which then moves mines just as it did when the game was played.
+    pub fn load_layout(&mut self, layout: Vec<Vec<i32>>) {
+        self.map = layout;
+        self.tiles = self
+            .map
+            .iter()
+            .map(|column| vec![Tile::Unopened; column.len()])
+            .collect();
+        self.flags = self
+            .map
+            .iter()
+            .map(|column| vec![0; column.len()])
+            .collect();
+    }
+
+    pub fn width(&self) -> u32 {
+        self.map.len() as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.map.first().map_or(0, |column| column.len()) as u32
+    }
+
+    pub fn coords(&self) -> impl Iterator<Item = (u32, u32)> {
+        let height = self.height();
+        (0..self.width()).flat_map(move |x| (0..height).map(move |y| (x, y)))
+    }
+
+    pub fn get(&self, coord: (u32, u32)) -> i32 {
+        let (x, y) = (coord.0 as usize, coord.1 as usize);
+        if let Some(x_val) = self.map.get(x) {
+            if let Some(y_val) = x_val.get(y) {
+                return *y_val;
+            }
+        }
+        0
+    }
+
+    /// Whether opening the tile loses the game, for a mine or an anti-mine.
+    pub fn is_bomb_at(&self, coord: (u32, u32)) -> bool {
+        self.get(coord) != 0
+    }
+
+    pub fn is_anti_mine_at(&self, coord: (u32, u32)) -> bool {
+        self.get(coord) < 0
+    }
+
+    pub fn bomb_count_at(&self, coord: (u32, u32)) -> i32 {
+        if self.is_bomb_at(coord) {
+            return 0;
+        }
+        self.safe_square_at(coord)
+            .into_iter()
+            .map(|coord| self.get(coord))
+            .sum()
+    }
+
+    pub fn flags_at(&self, coord: (u32, u32)) -> u32 {
+        self.flags
+            .get(coord.0 as usize)
+            .and_then(|column| column.get(coord.1 as usize))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn safe_square_at(&self, coord: (u32, u32)) -> Vec<(u32, u32)> {
+        let (width, height) = (self.width() as i32, self.height() as i32);
+        let wraps = self.options.wraps();
+        let mut around: Vec<(u32, u32)> = Vec::new();
+        for offset in self.neighbour_offsets(coord) {
+            let (mut x, mut y) = (
+                coord.0 as i32 + offset.0 as i32,
+                coord.1 as i32 + offset.1 as i32,
+            );
+            if wraps {
+                x = x.rem_euclid(width);
+                y = y.rem_euclid(height);
+            } else if x < 0 || y < 0 || x >= width || y >= height {
+                continue;
+            }
+            // On a narrow torus several offsets can land on the same cell.
+            let neighbour = (x as u32, y as u32);
+            if neighbour != coord && !around.contains(&neighbour) {
+                around.push(neighbour);
+            }
+        }
+        around
+    }
+
+    fn neighbour_offsets(&self, coord: (u32, u32)) -> &'static [(i8, i8)] {
+        match self.options.shape {
+            Shape::Square => match self.options.neighbourhood {
+                Neighbourhood::Moore => &SQUARE_COORD,
+                Neighbourhood::Knight => &KNIGHT_COORD,
+                Neighbourhood::Cross => &CROSS_COORD,
+                Neighbourhood::Ring => &RING_COORD,
+            },
+            Shape::Hex => &HEX_COORD,
+            Shape::Triangle if points_up(coord) => &TRIANGLE_UP_COORD,
+            Shape::Triangle => &TRIANGLE_DOWN_COORD,
+        }
+    }
+
+    pub fn tile(&self, coord: (u32, u32)) -> Option<Tile> {
+        self.tiles
+            .get(coord.0 as usize)
+            .and_then(|column| column.get(coord.1 as usize))
+            .copied()
+    }
+
+    /// Puts `flags` flags on a hidden tile, unmarking it when zero.
+    fn set_flags(&mut self, coord: (u32, u32), flags: u32) {
+        if let Some(f) = self
+            .flags
+            .get_mut(coord.0 as usize)
+            .and_then(|column| column.get_mut(coord.1 as usize))
+        {
+            *f = flags;
+        }
+        self.set_tile(
+            coord,
+            if flags > 0 {
+                Tile::Marked
+            } else {
+                Tile::Unopened
+            },
+        );
+    }
+
+    fn set_tile(&mut self, coord: (u32, u32), tile: Tile) {
+        if let Some(t) = self
+            .tiles
+            .get_mut(coord.0 as usize)
+            .and_then(|column| column.get_mut(coord.1 as usize))
+        {
+            *t = tile;
+        }
+    }
+
+    /// The number of flags placed, counting every flag on a stacked tile.
+    pub fn marked_count(&self) -> u32 {
+        self.flags.iter().flatten().sum()
+    }
+
+    /// Opens an unopened tile, flooding through zeros, or chords an opened number.
+    pub fn uncover(&mut self, coord: (u32, u32)) {
+        if self.status != GameStatus::Playing {
+            return;
+        }
+        match self.tile(coord) {
+            Some(Tile::Unopened | Tile::Questioned) => {
+                if !self.started {
+                    self.first_step(coord);
+                }
+                self.open(coord);
+                self.check();
+            }
+            Some(Tile::Opened) => self.chord(coord),
+            _ => (),
+        }
+    }
+
+    /// Opens every unmarked neighbour once the number already has enough marks around it.
+    pub fn chord(&mut self, coord: (u32, u32)) {
+        if self.status != GameStatus::Playing || self.tile(coord) != Some(Tile::Opened) {
+            return;
+        }
+        let around = self.safe_square_at(coord);
+        let marked_count = around
+            .iter()
+            .map(|pos| match self.tile(*pos) {
+                Some(Tile::AntiMarked) => -1,
+                _ => self.flags_at(*pos) as i32,
+            })
+            .sum::<i32>();
+        // With anti-mines a zero can hide mines, so chording needs at least one mark.
+        let has_marks = around
+            .iter()
+            .any(|pos| matches!(self.tile(*pos), Some(Tile::Marked | Tile::AntiMarked)));
+
+        if has_marks && marked_count == self.bomb_count_at(coord) {
+            for pos in around {
+                if self.status == GameStatus::Playing
+                    && self.tile(pos).is_some_and(|t| t.is_hidden())
+                {
+                    self.open(pos);
+                }
+            }
+        }
+        self.check();
+    }
+
+    pub fn mark(&mut self, coord: (u32, u32)) {
+        if self.status != GameStatus::Playing {
+            return;
+        }
+        match self.tile(coord) {
+            Some(Tile::Unopened) => self.set_flags(coord, 1),
+            Some(Tile::Marked)
+                if self.options.multi_mine && self.flags_at(coord) < MAX_MINES_PER_CELL =>
+            {
+                self.set_flags(coord, self.flags_at(coord) + 1)
+            }
+            Some(Tile::Marked) if self.options.anti_mine_count > 0 => {
+                self.set_flags(coord, 0);
+                self.set_tile(coord, Tile::AntiMarked);
+            }
+            Some(Tile::Marked | Tile::AntiMarked) if self.options.question_marks => {
+                self.set_flags(coord, 0);
+                self.set_tile(coord, Tile::Questioned);
+            }
+            Some(Tile::Marked | Tile::AntiMarked | Tile::Questioned) => self.set_flags(coord, 0),
+            _ => (),
+        }
+    }
+
+    fn first_step(&mut self, coord: (u32, u32)) {
+        // The solver only understands plain numbers.
+        if !self.options.no_guess || !self.options.plain_numbers() {
+            self.started = true;
+            self.protect_first_click(coord, self.options.first_click);
+            return;
+        }
+
+        // Generating clears the board, so marks placed before the first click are put back.
+        let (tiles, flags) = (self.tiles.clone(), self.flags.clone());
+        let seed = self.seed;
+        let area = self.options.area() as u64;
+        let attempts = if self.options.bomb_count as f32 > area as f32 * NO_GUESS_MAX_DENSITY {
+            0
+        } else {
+            NO_GUESS_ATTEMPTS.min(NO_GUESS_WORK / (area * area))
+        };
+
+        // Each attempt gets its own seed drawn from the player's, so that nearby seeds
+        // don't walk through the same candidates. Logic needs an opening to start
+        // from, whatever the first click policy says.
+        let mut seeds = StdRng::seed_from_u64(seed);
+        let solved = (0..attempts).any(|_| {
+            self.generate(seeds.gen());
+            self.started = true;
+            self.protect_first_click(coord, FirstClick::Opening);
+            solver::is_solvable(self, coord)
+        });
+        if !solved {
+            self.generate(seed);
+            self.started = true;
+            self.protect_first_click(coord, self.options.first_click);
+        }
+        self.seed = seed;
+        self.no_guess_failed = !solved;
+        self.tiles = tiles;
+        self.flags = flags;
+    }
+
+    /// Moves mines out of the first tile (and its neighbours for an opening)
+    /// into free room elsewhere, keeping the rest of the layout as generated.
+    fn protect_first_click(&mut self, coord: (u32, u32), first_click: FirstClick) {
+        let mut zone = match first_click {
+            FirstClick::Unprotected => return,
+            FirstClick::Safe => vec![coord],
+            FirstClick::Opening => {
+                let mut zone = self.safe_square_at(coord);
+                zone.push(coord);
+                zone
+            }
+        };
+
+        let cells = self.coords().collect::<Vec<_>>();
+        let mut free = self.free_slots(&cells, &zone);
+        let moving = |board: &Board, zone: &[(u32, u32)]| {
+            zone.iter()
+                .map(|pos| board.get(*pos).unsigned_abs() as usize)
+                .sum::<usize>()
+        };
+        if moving(self, &zone) > free.len() {
+            zone = vec![coord];
+            free = self.free_slots(&cells, &zone);
+        }
+        free.shuffle(&mut StdRng::seed_from_u64(self.seed));
+
+        let per_cell = self.mines_per_cell();
+        for pos in zone {
+            while self.is_bomb_at(pos) {
+                let Some(to) = free.pop() else {
+                    return;
+                };
+                // A slot is gone once an anti-mine took the cell or a mine filled it.
+                let (from, into) = (self.get(pos), self.get(to));
+                let fits = if from < 0 {
+                    into == 0
+                } else {
+                    (0..per_cell).contains(&into)
+                };
+                if fits {
+                    self.map[to.0 as usize][to.1 as usize] += from.signum();
+                    self.map[pos.0 as usize][pos.1 as usize] -= from.signum();
+                }
+            }
+        }
+    }
+
+    fn mines_per_cell(&self) -> i32 {
+        if self.options.multi_mine {
+            MAX_MINES_PER_CELL as i32
+        } else {
+            1
+        }
+    }
+
+    /// One entry per mine that still fits in a cell outside `zone`.
+    fn free_slots(&self, cells: &[(u32, u32)], zone: &[(u32, u32)]) -> Vec<(u32, u32)> {
+        let per_cell = self.mines_per_cell();
+        cells
+            .iter()
+            .filter(|pos| !zone.contains(pos) && !self.is_anti_mine_at(**pos))
+            .flat_map(|pos| std::iter::repeat_n(*pos, (per_cell - self.get(*pos)) as usize))
+            .collect()
+    }
+
+    fn open(&mut self, coord: (u32, u32)) {
+        let mut stack = vec![coord];
+        while let Some(pos) = stack.pop() {
+            if !self.tile(pos).is_some_and(|tile| tile.is_hidden()) {
+                continue;
+            }
+            self.set_tile(pos, Tile::Opened);
+
+            if self.is_bomb_at(pos) {
+                self.status = GameStatus::Lost;
+                self.exploded = Some(pos);
+                return;
+            }
+            // Mines and anti-mines can cancel out, so a zero alone is not enough to flood.
+            let around = self.safe_square_at(pos);
+            if around.iter().all(|pos| !self.is_bomb_at(*pos)) {
+                stack.extend(around);
+            }
+        }
+    }
+
+    fn check(&mut self) {
+        if self.status != GameStatus::Playing {
+            return;
+        }
+        let won = self
+            .tiles
+            .iter()
+            .flatten()
+            .zip(self.map.iter().flatten())
+            .all(|(tile, bombs)| *tile == Tile::Opened || *bombs != 0);
+
+        if won {
+            self.status = GameStatus::Won;
+            for coord in self.coords().collect::<Vec<_>>() {
+                match self.get(coord) {
+                    _ if self.tile(coord) == Some(Tile::Opened) => (),
+                    bombs if bombs < 0 => {
+                        self.set_flags(coord, 0);
+                        self.set_tile(coord, Tile::AntiMarked);
+                    }
+                    bombs => self.set_flags(coord, bombs as u32),
+                }
+            }
+        }
+    }
+
+    pub fn console_output(&self) -> String {
+        let separator: String = (0..=self.width() * 3).map(|_| '-').collect();
+        let mut board = vec![];
+
+        for y in 0..self.height() as usize {
+            let mut row = vec![];
+            for x in 0..self.width() as usize {
+                let column = self.map[x][y];
+                row.push(format!("{:2}", column));
+            }
+            board.push(format!("|{}|", row.join(" ")));
+        }
+        board.reverse();
+        format!("{}\n{}\n{}", separator, board.join("\n"), separator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_with(width: u32, height: u32, bombs: &[(u32, u32)]) -> Board {
+        let mut board = Board::default();
+        board.reset(&BoardOptions::new(width, height, 0).with_seed(Some(0)));
+        for (x, y) in bombs {
+            board.map[*x as usize][*y as usize] = 1;
+        }
+        board.options.bomb_count = bombs.len() as u32;
+        board.options.first_click = FirstClick::Unprotected;
+        board
+    }
+
+    fn check_geometry(width: u32, height: u32) {
+        let mut board = Board::default();
+        board.reset(&BoardOptions::new(width, height, width * height / 4).with_seed(Some(7)));
+
+        assert_eq!(board.width(), width);
+        assert_eq!(board.height(), height);
+        assert_eq!(board.coords().count() as u32, width * height);
+        assert_eq!(
+            board.map.iter().flatten().sum::<i32>(),
+            (width * height / 4) as i32
+        );
+
+        for (x, y) in board.coords() {
+            let around = board.safe_square_at((x, y));
+            let expected = (-1i32..=1)
+                .flat_map(|dx| (-1i32..=1).map(move |dy| (dx, dy)))
+                .filter(|d| *d != (0, 0))
+                .map(|(dx, dy)| (x as i32 + dx, y as i32 + dy))
+                .filter(|(nx, ny)| {
+                    *nx >= 0 && *ny >= 0 && *nx < width as i32 && *ny < height as i32
+                })
+                .count();
+            assert_eq!(around.len(), expected, "neighbours of ({x},{y})");
+            assert!(around.iter().all(|(nx, ny)| *nx < width && *ny < height));
+        }
+
+        let output = board.console_output();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len() as u32, height + 2);
+        assert_eq!(lines[1].len() as u32, width * 3 + 1);
+    }
+
+    #[test]
+    fn tall_board_geometry() {
+        check_geometry(5, 12);
+    }
+
+    #[test]
+    fn wide_board_geometry() {
+        check_geometry(30, 16);
+        check_geometry(12, 5);
+    }
+
+    #[test]
+    fn single_row_and_column_geometry() {
+        check_geometry(1, 9);
+        check_geometry(9, 1);
+    }
+
+    #[test]
+    fn wide_board_counts_use_both_dimensions() {
+        // Mines in the far corner of a 6x2 board only touch their own corner.
+        let board = board_with(6, 2, &[(5, 1)]);
+        assert_eq!(board.bomb_count_at((4, 0)), 1);
+        assert_eq!(board.bomb_count_at((4, 1)), 1);
+        assert_eq!(board.bomb_count_at((5, 0)), 1);
+        assert_eq!(board.bomb_count_at((3, 1)), 0);
+        assert!(board.safe_square_at((0, 1)).iter().all(|(_, y)| *y < 2));
+    }
+
+    #[test]
+    fn flood_fill_reaches_the_end_of_a_line() {
+        let mut board = board_with(1, 10, &[(0, 9)]);
+        board.uncover((0, 0));
+        assert_eq!(board.tile((0, 8)), Some(Tile::Opened));
+        assert_eq!(board.tile((0, 9)), Some(Tile::Marked));
+        assert_eq!(board.status, GameStatus::Won);
+
+        let mut board = board_with(10, 1, &[(0, 0)]);
+        board.uncover((9, 0));
+        assert_eq!(board.status, GameStatus::Won);
+    }
+
+    #[test]
+    fn tall_board_can_be_won() {
+        let mut board = board_with(3, 7, &[(0, 6), (2, 0)]);
+        board.uncover((2, 6));
+        for coord in board.coords().collect::<Vec<_>>() {
+            if !board.is_bomb_at(coord) {
+                board.uncover(coord);
+            }
+        }
+        assert_eq!(board.status, GameStatus::Won);
+        assert_eq!(board.marked_count(), 2);
+    }
+
+    #[test]
+    fn options_must_make_a_board() {
+        assert!(BoardOptions::try_new(30, 16, 99).is_ok());
+        assert_eq!(
+            BoardOptions::try_new(0, 9, 0),
+            Err(OptionsError::EmptyBoard)
+        );
+        assert_eq!(
+            BoardOptions::try_new(3, 3, 9),
+            Err(OptionsError::TooManyMines {
+                bomb_count: 9,
+                most: 8
+            })
+        );
+        assert!(matches!(
+            BoardOptions::try_new(u32::MAX, 2, 1),
+            Err(OptionsError::TooLarge { .. })
+        ));
+
+        let mut options = BoardOptions::new(3, 3, 9);
+        options.multi_mine = true;
+        assert_eq!(options.validate(), Ok(()));
+        options.anti_mine_count = 1;
+        assert_eq!(
+            options.validate(),
+            Err(OptionsError::TooManyAntiMines {
+                anti_mine_count: 1,
+                most: 0
+            })
+        );
+    }
+
+    #[test]
+    fn full_multi_mine_board_keeps_the_first_click_safe() {
+        for seed in 0..20 {
+            let mut board = Board::default();
+            board.reset(&BoardOptions {
+                first_click: FirstClick::Safe,
+                multi_mine: true,
+                ..BoardOptions::new(3, 3, 24).with_seed(Some(seed))
+            });
+            board.uncover((1, 1));
+
+            assert_eq!(board.status, GameStatus::Won);
+            assert_eq!(board.get((1, 1)), 0);
+            assert_eq!(board.map.iter().flatten().sum::<i32>(), 24);
+        }
+    }
+
+    #[test]
+    fn no_guess_layout_solves_from_the_first_click() {
+        let mut layouts = Vec::new();
+        for seed in 0..5 {
+            let mut board = Board::default();
+            board.reset(&BoardOptions {
+                no_guess: true,
+                ..BoardOptions::new(9, 9, 10).with_seed(Some(seed))
+            });
+            board.mark((0, 0));
+            board.uncover((4, 4));
+
+            assert!(!board.no_guess_failed);
+            assert_eq!(board.seed, seed);
+            assert_eq!(board.tile((0, 0)), Some(Tile::Marked));
+            assert_eq!(board.map.iter().flatten().sum::<i32>(), 10);
+            let mut layout = Board {
+                options: board.options.clone(),
+                ..Board::default()
+            };
+            layout.load_layout(board.map.clone());
+            layout.started = true;
+            assert!(solver::is_solvable(&layout, (4, 4)));
+            layouts.push(board.map);
+        }
+
+        // Neighbouring seeds give different boards, and the same seed the same one.
+        for (i, layout) in layouts.iter().enumerate() {
+            assert!(!layouts[..i].contains(layout));
+        }
+        let mut board = Board::default();
+        board.reset(&BoardOptions {
+            no_guess: true,
+            ..BoardOptions::new(9, 9, 10).with_seed(Some(2))
+        });
+        board.uncover((4, 4));
+        assert_eq!(board.map, layouts[2]);
+    }
+
+    #[test]
+    fn dense_no_guess_board_falls_back() {
+        let mut board = Board::default();
+        board.reset(&BoardOptions {
+            no_guess: true,
+            first_click: FirstClick::Safe,
+            ..BoardOptions::new(5, 5, 12).with_seed(Some(3))
+        });
+        board.mark((0, 0));
+        board.uncover((2, 2));
+
+        assert!(board.no_guess_failed);
+        assert_ne!(board.status, GameStatus::Lost);
+        assert_eq!(board.tile((0, 0)), Some(Tile::Marked));
+        assert_eq!(board.map.iter().flatten().sum::<i32>(), 12);
+    }
+
+    #[test]
+    fn first_click_protection() {
+        for seed in 0..10 {
+            let options = |first_click| BoardOptions {
+                first_click,
+                ..BoardOptions::new(9, 9, 30).with_seed(Some(seed))
+            };
+            let mut board = Board::default();
+            board.reset(&options(FirstClick::Unprotected));
+            let generated = board.map.clone();
+            // A mined cell, so every policy has something to move.
+            let coord = board.coords().find(|pos| board.is_bomb_at(*pos)).unwrap();
+
+            board.uncover(coord);
+            assert_eq!(board.map, generated);
+            assert_eq!(board.status, GameStatus::Lost);
+
+            board.reset(&options(FirstClick::Safe));
+            board.uncover(coord);
+            assert!(!board.is_bomb_at(coord));
+            assert_ne!(board.status, GameStatus::Lost);
+            assert_eq!(board.map.iter().flatten().sum::<i32>(), 30);
+
+            board.reset(&options(FirstClick::Opening));
+            board.uncover(coord);
+            assert_eq!(board.bomb_count_at(coord), 0);
+            assert!(board
+                .safe_square_at(coord)
+                .into_iter()
+                .chain([coord])
+                .all(|pos| !board.is_bomb_at(pos)));
+            assert_eq!(board.map.iter().flatten().sum::<i32>(), 30);
+        }
+    }
+
+    #[test]
+    fn seed_fixes_the_layout() {
+        let layout = |seed| {
+            let mut board = Board::default();
+            board.reset(&BoardOptions::new(16, 16, 40).with_seed(Some(seed)));
+            assert_eq!(board.seed, seed);
+            board.map
+        };
+        assert_eq!(layout(42), layout(42));
+        assert_ne!(layout(42), layout(43));
+    }
+
+    /// Neighbour counts at the corners, the middle of the bottom and left edges and the centre.
+    fn neighbour_counts(options: BoardOptions, coords: &[(u32, u32)]) -> Vec<usize> {
+        let mut board = Board::default();
+        board.reset(&options.with_seed(Some(0)));
+        coords
+            .iter()
+            .map(|coord| board.safe_square_at(*coord).len())
+            .collect()
+    }
+
+    const SQUARE_SPOTS: [(u32, u32); 7] = [(0, 0), (6, 0), (0, 6), (6, 6), (3, 0), (0, 3), (3, 3)];
+
+    #[test]
+    fn hex_neighbour_counts() {
+        let options = BoardOptions {
+            shape: Shape::Hex,
+            ..BoardOptions::new(7, 7, 5)
+        };
+        // The rhombus has a sharp corner at (0, 0) and (6, 6) and a wide one at the others.
+        assert_eq!(
+            neighbour_counts(options, &SQUARE_SPOTS),
+            [2, 3, 3, 2, 4, 4, 6]
+        );
+    }
+
+    const TRIANGLE_SPOTS: [(u32, u32); 9] = [
+        (0, 0),
+        (7, 0),
+        (0, 7),
+        (7, 7),
+        (3, 0),
+        (4, 0),
+        (0, 3),
+        (4, 4),
+        (3, 4),
+    ];
+
+    #[test]
+    fn triangle_neighbour_counts() {
+        let options = BoardOptions {
+            shape: Shape::Triangle,
+            ..BoardOptions::new(8, 8, 5)
+        };
+        // Along the bottom edge a down triangle loses the three cells around its apex
+        // and an up one the five along its base.
+        assert_eq!(
+            neighbour_counts(options, &TRIANGLE_SPOTS),
+            [4, 5, 4, 5, 9, 7, 7, 12, 12]
+        );
+    }
+
+    #[test]
+    fn wrapped_neighbour_counts() {
+        let wrapped = |shape, neighbourhood, size| BoardOptions {
+            shape,
+            neighbourhood,
+            wrap: true,
+            ..BoardOptions::new(size, size, 5)
+        };
+        for (neighbourhood, count) in [
+            (Neighbourhood::Moore, 8),
+            (Neighbourhood::Knight, 8),
+            (Neighbourhood::Cross, 4),
+            (Neighbourhood::Ring, 16),
+        ] {
+            let options = wrapped(Shape::Square, neighbourhood, 7);
+            assert_eq!(neighbour_counts(options, &SQUARE_SPOTS), [count; 7]);
+        }
+        let options = wrapped(Shape::Hex, Neighbourhood::Moore, 7);
+        assert_eq!(neighbour_counts(options, &SQUARE_SPOTS), [6; 7]);
+        let options = wrapped(Shape::Triangle, Neighbourhood::Moore, 8);
+        assert_eq!(neighbour_counts(options, &TRIANGLE_SPOTS), [12; 9]);
+
+        // Odd triangle boards can't wrap, so they keep their edges.
+        let options = wrapped(Shape::Triangle, Neighbourhood::Moore, 7);
+        assert_eq!(neighbour_counts(options, &[(0, 0), (3, 3)]), [4, 12]);
+        // On a torus smaller than the neighbourhood, offsets that land on the same
+        // cell count once.
+        let options = wrapped(Shape::Square, Neighbourhood::Ring, 3);
+        assert_eq!(neighbour_counts(options, &[(0, 0), (1, 1)]), [8, 8]);
+    }
+
+    #[test]
+    fn square_neighbourhood_counts() {
+        for (neighbourhood, counts) in [
+            (Neighbourhood::Moore, [3, 3, 3, 3, 5, 5, 8]),
+            (Neighbourhood::Knight, [2, 2, 2, 2, 4, 4, 8]),
+            (Neighbourhood::Cross, [2, 2, 2, 2, 3, 3, 4]),
+            (Neighbourhood::Ring, [5, 5, 5, 5, 9, 9, 16]),
+        ] {
+            let options = BoardOptions {
+                neighbourhood,
+                ..BoardOptions::new(7, 7, 5)
+            };
+            assert_eq!(neighbour_counts(options, &SQUARE_SPOTS), counts);
+        }
+    }
+
+    #[test]
+    fn marks_cycle_through_every_state() {
+        let mut board = board_with(3, 3, &[(0, 0)]);
+        board.options.anti_mine_count = 1;
+        board.options.question_marks = true;
+        board.options.multi_mine = true;
+
+        let mut seen = vec![];
+        for _ in 0..7 {
+            board.mark((2, 2));
+            seen.push((board.tile((2, 2)).unwrap(), board.flags_at((2, 2))));
+        }
+        assert_eq!(
+            seen,
+            [
+                (Tile::Marked, 1),
+                (Tile::Marked, 2),
+                (Tile::Marked, 3),
+                (Tile::AntiMarked, 0),
+                (Tile::Questioned, 0),
+                (Tile::Unopened, 0),
+                (Tile::Marked, 1),
+            ]
+        );
+
+        // Without the variants a flag goes straight back to unmarked.
+        let mut board = board_with(3, 3, &[(0, 0)]);
+        board.mark((2, 2));
+        board.mark((2, 2));
+        assert_eq!(board.tile((2, 2)), Some(Tile::Unopened));
+        // A "?" can still be opened.
+        board.options.question_marks = true;
+        board.mark((1, 1));
+        board.mark((1, 1));
+        assert_eq!(board.tile((1, 1)), Some(Tile::Questioned));
+        board.uncover((1, 1));
+        assert_eq!(board.tile((1, 1)), Some(Tile::Opened));
+    }
+
+    #[test]
+    fn chord_counts_stacked_flags() {
+        let mut board = board_with(3, 3, &[]);
+        board.options.multi_mine = true;
+        board.options.bomb_count = 2;
+        board.map[0][0] = 2;
+        board.started = true;
+
+        board.uncover((1, 1));
+        assert_eq!(board.bomb_count_at((1, 1)), 2);
+        board.mark((0, 0));
+        board.chord((1, 1));
+        assert_eq!(board.tile((2, 2)), Some(Tile::Unopened));
+
+        board.mark((0, 0));
+        assert_eq!(board.flags_at((0, 0)), 2);
+        board.chord((1, 1));
+        assert_eq!(board.status, GameStatus::Won);
+        assert_eq!(board.marked_count(), 2);
+    }
+
+    #[test]
+    fn reset_starts_a_fresh_game() {
+        let mut board = board_with(4, 4, &[(0, 0)]);
+        board.mark((3, 3));
+        board.uncover((0, 0));
+        assert_eq!(board.status, GameStatus::Lost);
+
+        board.reset(&BoardOptions::new(5, 3, 4).with_seed(Some(9)));
+        assert_eq!((board.width(), board.height()), (5, 3));
+        assert_eq!(board.map.iter().flatten().sum::<i32>(), 4);
+        assert!(board
+            .tiles
+            .iter()
+            .flatten()
+            .all(|tile| *tile == Tile::Unopened));
+        assert_eq!(board.marked_count(), 0);
+        assert_eq!(board.status, GameStatus::Playing);
+        assert_eq!(board.exploded, None);
+        assert_eq!(board.seed, 9);
+        assert!(!board.started && !board.assisted);
+    }
+
+    #[test]
+    fn flood_fill_stops_at_numbers() {
+        // The mines wall off the top right corner, so the opening can't reach it.
+        let mut board = board_with(5, 5, &[(2, 2), (2, 3), (2, 4), (3, 2), (4, 2)]);
+        board.uncover((0, 0));
+
+        assert_eq!(board.tile((1, 1)), Some(Tile::Opened));
+        assert_eq!(board.bomb_count_at((1, 1)), 1);
+        assert_eq!(board.tile((0, 4)), Some(Tile::Opened));
+        assert_eq!(board.tile((4, 0)), Some(Tile::Opened));
+        assert_eq!(board.tile((4, 4)), Some(Tile::Unopened));
+        assert_eq!(board.tile((3, 3)), Some(Tile::Unopened));
+        assert_eq!(board.status, GameStatus::Playing);
+
+        board.uncover((3, 3));
+        assert_eq!(board.tile((4, 4)), Some(Tile::Unopened));
+        board.uncover((4, 4));
+        board.uncover((3, 4));
+        board.uncover((4, 3));
+        assert_eq!(board.status, GameStatus::Won);
+    }
+
+    #[test]
+    fn losing_ends_the_game() {
+        let mut board = board_with(3, 3, &[(1, 1)]);
+        board.uncover((0, 0));
+        assert_eq!(board.tile((0, 1)), Some(Tile::Unopened));
+        board.uncover((1, 1));
+
+        assert_eq!(board.status, GameStatus::Lost);
+        assert_eq!(board.exploded, Some((1, 1)));
+        // Nothing moves once the game is over.
+        board.uncover((2, 2));
+        board.mark((2, 1));
+        assert_eq!(board.tile((2, 2)), Some(Tile::Unopened));
+        assert_eq!(board.tile((2, 1)), Some(Tile::Unopened));
+    }
+}
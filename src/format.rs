@@ -0,0 +1,350 @@
+//! Board files that other tools can read: a plain text grid and the binary MBF
+//! format used by most Minesweeper clones. Both only hold plain boards, one mine
+//! per cell and no anti-mines.
+//!
+//! In the text grid the top line is the highest row, like `Board::console_output`:
+//!
+//! * `.` covered, `*` covered mine
+//! * `f` flag on a safe cell, `F` flag on a mine
+//! * `0`-`9` opened cell (`+` for ten or more), `X` the mine that went off
+
+use std::fmt;
+
+use crate::engine::{Board, BoardOptions, GameStatus, OptionsError, Tile};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// A problem in a text board, with 1-based line and column.
+    Text {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A problem in MBF data at a byte offset.
+    Binary { offset: usize, message: String },
+    /// A well-formed board that can't be played, such as one that is all mines.
+    Options(OptionsError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Text {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            ParseError::Binary { offset, message } => {
+                write!(f, "byte {}: {}", offset, message)
+            }
+            ParseError::Options(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<OptionsError> for ParseError {
+    fn from(error: OptionsError) -> Self {
+        ParseError::Options(error)
+    }
+}
+
+fn text_error(line: usize, column: usize, message: impl Into<String>) -> ParseError {
+    ParseError::Text {
+        line,
+        column,
+        message: message.into(),
+    }
+}
+
+fn binary_error(offset: usize, message: impl Into<String>) -> ParseError {
+    ParseError::Binary {
+        offset,
+        message: message.into(),
+    }
+}
+
+/// A board ready to play with the given mines, marks and opened cells.
+fn board_from(
+    map: Vec<Vec<i32>>,
+    tiles: Vec<Vec<Tile>>,
+    flags: Vec<Vec<u32>>,
+    exploded: Option<(u32, u32)>,
+) -> Result<Board, OptionsError> {
+    let width = map.len() as u32;
+    let height = map.first().map_or(0, |column| column.len() as u32);
+    let bomb_count = map.iter().flatten().sum::<i32>() as u32;
+    let options = BoardOptions::try_new(width, height, bomb_count)?;
+    Ok(Board {
+        map,
+        tiles,
+        flags,
+        status: if exploded.is_some() {
+            GameStatus::Lost
+        } else {
+            GameStatus::Playing
+        },
+        exploded,
+        seed: 0,
+        options,
+        // The layout is fixed, so the first click must not move any mine.
+        started: true,
+        assisted: false,
+        no_guess_failed: false,
+        restored: false,
+    })
+}
+
+pub fn parse_text(text: &str) -> Result<Board, ParseError> {
+    let rows = text
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .collect::<Vec<_>>();
+    // Trailing blank lines are left by editors and mean nothing.
+    let row_count = rows
+        .iter()
+        .rposition(|row| !row.is_empty())
+        .map_or(0, |i| i + 1);
+    if row_count == 0 {
+        return Err(text_error(1, 1, "the board is empty"));
+    }
+
+    let width = rows[0].chars().count();
+    let height = row_count;
+    let mut map = vec![vec![0; height]; width];
+    let mut tiles = vec![vec![Tile::Unopened; height]; width];
+    let mut flags = vec![vec![0; height]; width];
+    let mut exploded = None;
+
+    for (line, row) in rows[..row_count].iter().enumerate() {
+        let y = height - 1 - line;
+        let mut cells = 0;
+        for (x, cell) in row.chars().enumerate() {
+            if x >= width {
+                return Err(text_error(
+                    line + 1,
+                    x + 1,
+                    format!("row is longer than the first row ({} cells)", width),
+                ));
+            }
+            let (mine, tile) = match cell {
+                '.' => (false, Tile::Unopened),
+                '*' => (true, Tile::Unopened),
+                'f' => (false, Tile::Marked),
+                'F' => (true, Tile::Marked),
+                '0'..='9' | '+' => (false, Tile::Opened),
+                'X' => {
+                    if exploded.is_some() {
+                        return Err(text_error(line + 1, x + 1, "only one mine can go off"));
+                    }
+                    exploded = Some((x as u32, y as u32));
+                    (true, Tile::Opened)
+                }
+                other => {
+                    return Err(text_error(
+                        line + 1,
+                        x + 1,
+                        format!("unknown cell '{}'", other),
+                    ))
+                }
+            };
+            map[x][y] = mine as i32;
+            tiles[x][y] = tile;
+            flags[x][y] = (tile == Tile::Marked) as u32;
+            cells += 1;
+        }
+        if cells < width {
+            return Err(text_error(
+                line + 1,
+                cells + 1,
+                format!("row is shorter than the first row ({} cells)", width),
+            ));
+        }
+    }
+
+    Ok(board_from(map, tiles, flags, exploded)?)
+}
+
+/// The board as a text grid, or `None` when it isn't a plain board.
+pub fn to_text(board: &Board) -> Option<String> {
+    if !board.options.plain_numbers() {
+        return None;
+    }
+
+    let mut rows = vec![];
+    for y in (0..board.height()).rev() {
+        let row = (0..board.width())
+            .map(|x| {
+                let coord = (x, y);
+                let mine = board.is_bomb_at(coord);
+                match board.tile(coord).unwrap_or_default() {
+                    Tile::Opened if mine => 'X',
+                    Tile::Opened => match board.bomb_count_at(coord) {
+                        count @ 0..=9 => char::from(b'0' + count as u8),
+                        _ => '+',
+                    },
+                    Tile::Marked if mine => 'F',
+                    Tile::Marked => 'f',
+                    _ if mine => '*',
+                    _ => '.',
+                }
+            })
+            .collect::<String>();
+        rows.push(row);
+    }
+    Some(rows.join("\n") + "\n")
+}
+
+/// Reads MBF: width and height as one byte each, the mine count as a big-endian
+/// `u16`, then one `x, y` byte pair per mine with `y` counted from the top.
+pub fn parse_mbf(data: &[u8]) -> Result<Board, ParseError> {
+    if data.len() < 4 {
+        return Err(binary_error(data.len(), "the header needs 4 bytes"));
+    }
+    let (width, height) = (data[0] as usize, data[1] as usize);
+    if width == 0 {
+        return Err(binary_error(0, "the width is 0"));
+    }
+    if height == 0 {
+        return Err(binary_error(1, "the height is 0"));
+    }
+    let mine_count = u16::from_be_bytes([data[2], data[3]]) as usize;
+    let expected = 4 + mine_count * 2;
+    if data.len() != expected {
+        return Err(binary_error(
+            data.len().min(expected),
+            format!(
+                "{} mines need {} bytes, found {}",
+                mine_count,
+                expected,
+                data.len()
+            ),
+        ));
+    }
+
+    let mut map = vec![vec![0; height]; width];
+    for (i, pair) in data[4..].chunks_exact(2).enumerate() {
+        let offset = 4 + i * 2;
+        let (x, row) = (pair[0] as usize, pair[1] as usize);
+        if x >= width {
+            return Err(binary_error(
+                offset,
+                format!("column {} is off the board", x),
+            ));
+        }
+        if row >= height {
+            return Err(binary_error(
+                offset + 1,
+                format!("row {} is off the board", row),
+            ));
+        }
+        let y = height - 1 - row;
+        if map[x][y] != 0 {
+            return Err(binary_error(
+                offset,
+                format!("mine ({}, {}) is listed twice", x, row),
+            ));
+        }
+        map[x][y] = 1;
+    }
+
+    let tiles = vec![vec![Tile::Unopened; height]; width];
+    let flags = vec![vec![0; height]; width];
+    Ok(board_from(map, tiles, flags, None)?)
+}
+
+/// The mines as MBF, or `None` when the board isn't plain or is too big for it.
+pub fn to_mbf(board: &Board) -> Option<Vec<u8>> {
+    if !board.options.plain_numbers() {
+        return None;
+    }
+    let width = u8::try_from(board.width()).ok()?;
+    let height = u8::try_from(board.height()).ok()?;
+    let mines = board
+        .coords()
+        .filter(|coord| board.is_bomb_at(*coord))
+        .collect::<Vec<_>>();
+
+    let mut data = vec![width, height];
+    data.extend(u16::try_from(mines.len()).ok()?.to_be_bytes());
+    for (x, y) in mines {
+        data.extend([x as u8, (board.height() - 1 - y) as u8]);
+    }
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        let text = "*..f\n12F.\nX+..\n";
+        let board = parse_text(text).unwrap();
+
+        assert_eq!((board.width(), board.height()), (4, 3));
+        assert_eq!(board.options.bomb_count, 3);
+        assert!(board.is_bomb_at((0, 2)));
+        assert!(board.is_bomb_at((2, 1)));
+        assert_eq!(board.tile((3, 2)), Some(Tile::Marked));
+        assert_eq!(board.exploded, Some((0, 0)));
+        assert_eq!(board.status, GameStatus::Lost);
+        // Opened numbers are written back from the mines, not copied from the input.
+        assert_eq!(to_text(&board).unwrap(), "*..f\n23F.\nX2..\n");
+    }
+
+    #[test]
+    fn text_errors_have_positions() {
+        assert_eq!(
+            parse_text("...\n.?.\n").unwrap_err(),
+            text_error(2, 2, "unknown cell '?'")
+        );
+        assert_eq!(
+            parse_text("...\n..\n").unwrap_err().to_string(),
+            "line 2, column 3: row is shorter than the first row (3 cells)"
+        );
+        assert!(matches!(
+            parse_text("...\n....\n"),
+            Err(ParseError::Text {
+                line: 2,
+                column: 4,
+                ..
+            })
+        ));
+        assert!(parse_text("\n\n").is_err());
+        assert_eq!(
+            parse_text("**\n**\n").unwrap_err(),
+            ParseError::Options(OptionsError::TooManyMines {
+                bomb_count: 4,
+                most: 3
+            })
+        );
+    }
+
+    #[test]
+    fn mbf_round_trip() {
+        let board = parse_text("*...\n..*.\n...*\n").unwrap();
+        let data = to_mbf(&board).unwrap();
+
+        assert_eq!(data[..4], [4, 3, 0, 3]);
+        // MBF counts rows from the top.
+        assert!(data[4..].chunks(2).any(|pair| pair == [0, 0]));
+        assert!(data[4..].chunks(2).any(|pair| pair == [3, 2]));
+        assert_eq!(parse_mbf(&data).unwrap().map, board.map);
+    }
+
+    #[test]
+    fn mbf_errors_have_offsets() {
+        assert_eq!(
+            parse_mbf(&[4, 3, 0, 2, 0, 0]).unwrap_err().to_string(),
+            "byte 6: 2 mines need 8 bytes, found 6"
+        );
+        assert_eq!(
+            parse_mbf(&[4, 3, 0, 1, 0, 3]).unwrap_err(),
+            binary_error(5, "row 3 is off the board")
+        );
+        assert_eq!(
+            parse_mbf(&[4, 3, 0, 2, 1, 1, 1, 1]).unwrap_err(),
+            binary_error(6, "mine (1, 1) is listed twice")
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::HintButton,
+    engine::FirstClick,
+    resources::{Board, Hint, Message, Settings},
+    solver,
+};
+
+pub fn hint_request_system(
+    keys: Res<Input<KeyCode>>,
+    button_query: Query<&Interaction, (With<HintButton>, Changed<Interaction>)>,
+    board: Res<Board>,
+    mut hint: ResMut<Hint>,
+    mut message: ResMut<Message>,
+    settings: Res<Settings>,
+) {
+    let pressed = keys.just_pressed(settings.keys.hint)
+        || button_query
+            .iter()
+            .any(|interaction| interaction == &Interaction::Pressed);
+    if !pressed {
+        return;
+    }
+
+    if !board.options.plain_numbers() {
+        message.0 = "Hints need plain mines, one per cell".to_string();
+        return;
+    }
+
+    if !board.started && board.options.first_click != FirstClick::Unprotected {
+        message.0 = "The first click is always safe".to_string();
+        return;
+    }
+
+    if let Some(deduction) = solver::deduce(&board).first() {
+        let (x, y) = deduction.coord;
+        hint.coord = Some(deduction.coord);
+        hint.certain = true;
+        hint.mine = deduction.mine;
+        message.0 = format!(
+            "{} at ({},{}): {}",
+            if deduction.mine { "Mine" } else { "Safe" },
+            x,
+            y,
+            deduction.explain(&board)
+        );
+    } else if let Some(((x, y), risk)) = solver::safest_guess(&board) {
+        hint.coord = Some((x, y));
+        hint.certain = false;
+        hint.mine = false;
+        message.0 = format!(
+            "No certain move, safest guess ({},{}) ~{:.0}% mine",
+            x,
+            y,
+            risk * 100.0
+        );
+    }
+}
+
+pub fn hint_clear_system(mut hint: ResMut<Hint>, mut message: ResMut<Message>, board: Res<Board>) {
+    if board.is_changed() && !hint.is_changed() && hint.coord.is_some() {
+        hint.coord = None;
+        message.0.clear();
+    }
+}
@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::GameButton,
+    over::face_and_state,
+    resources::{Board, History, LastStep, Playback, Settings, StartTime},
+    tile::{TileMarkEvent, TileUncoverEvent},
+    undo::Moment,
+    GameState,
+};
+
+fn moment(last_step: &LastStep, start_time: &StartTime) -> Moment {
+    Moment {
+        last_step: last_step.coord,
+        elapsed: start_time.0.elapsed(),
+    }
+}
+
+/// Saves the state before this frame's moves; `history_commit_system` keeps what they changed.
+pub fn history_record_system(
+    tile_uncover_events: EventReader<TileUncoverEvent>,
+    tile_mark_events: EventReader<TileMarkEvent>,
+    board: Res<Board>,
+    last_step: Res<LastStep>,
+    start_time: Res<StartTime>,
+    mut history: ResMut<History>,
+) {
+    if tile_uncover_events.is_empty() && tile_mark_events.is_empty() {
+        return;
+    }
+    history.begin(&board, moment(&last_step, &start_time));
+}
+
+pub fn history_commit_system(
+    mut tile_uncover_events: EventReader<TileUncoverEvent>,
+    mut tile_mark_events: EventReader<TileMarkEvent>,
+    board: Res<Board>,
+    last_step: Res<LastStep>,
+    start_time: Res<StartTime>,
+    mut history: ResMut<History>,
+) {
+    if tile_uncover_events.read().count() + tile_mark_events.read().count() == 0 {
+        return;
+    }
+    history.commit(&board, moment(&last_step, &start_time));
+}
+
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryEvent {
+    Undo,
+    Redo,
+}
+
+/// Ctrl+Z steps back one move, including a fatal one, and Ctrl+Y steps forward again.
+pub fn undo_redo_key_system(
+    keys: Res<Input<KeyCode>>,
+    playback: Res<Playback>,
+    settings: Res<Settings>,
+    mut history_event: EventWriter<HistoryEvent>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || playback.is_active() {
+        return;
+    }
+    if keys.just_pressed(settings.keys.undo) {
+        history_event.send(HistoryEvent::Undo);
+    } else if keys.just_pressed(settings.keys.redo) {
+        history_event.send(HistoryEvent::Redo);
+    }
+}
+
+/// Tile textures follow the restored `Board` through `sync_tiles_system`.
+pub fn undo_redo_system(
+    mut history_events: EventReader<HistoryEvent>,
+    mut button_query: Query<&mut UiTextureAtlasImage, With<GameButton>>,
+    mut board: ResMut<Board>,
+    mut last_step: ResMut<LastStep>,
+    mut start_time: ResMut<StartTime>,
+    mut history: ResMut<History>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    let mut restored = false;
+    for history_event in history_events.read() {
+        let now = moment(&last_step, &start_time);
+        let moment = match history_event {
+            HistoryEvent::Undo => history.undo(&mut board, now),
+            HistoryEvent::Redo => history.redo(&mut board, now),
+        };
+        let Some(moment) = moment else {
+            continue;
+        };
+
+        board.assisted = true;
+        last_step.coord = moment.last_step;
+        start_time.0.set_elapsed(moment.elapsed);
+        restored = true;
+    }
+    if !restored {
+        return;
+    }
+
+    let (face, state) = face_and_state(board.status);
+    if let Ok(mut button_image) = button_query.get_single_mut() {
+        button_image.index = face;
+    }
+    game_state.set(state);
+}
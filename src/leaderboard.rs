@@ -0,0 +1,170 @@
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::save::{read_ron, write_ron, SaveError};
+
+pub const LEADERBOARD_PATH: &str = "minesweeper-scores.ron";
+
+/// Places kept per board.
+pub const LEADERBOARD_SIZE: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    /// Seconds.
+    pub time: f32,
+    /// `YYYY-MM-DD`, in UTC.
+    pub date: String,
+    pub bbbv_per_second: f32,
+    pub seed: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Leaderboard {
+    /// Fastest first, keyed by `stats::category`.
+    pub boards: BTreeMap<String, Vec<Entry>>,
+}
+
+impl Leaderboard {
+    pub fn load() -> Result<Self, SaveError> {
+        read_ron(LEADERBOARD_PATH)
+    }
+
+    pub fn save(&self) -> Result<(), SaveError> {
+        write_ron(LEADERBOARD_PATH, self)
+    }
+
+    pub fn entries(&self, category: &str) -> &[Entry] {
+        self.boards.get(category).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether a win in `time` seconds would get a place.
+    pub fn qualifies(&self, category: &str, time: f32) -> bool {
+        let entries = self.entries(category);
+        entries.len() < LEADERBOARD_SIZE || entries.last().is_some_and(|last| time < last.time)
+    }
+
+    /// Puts the entry in its place, dropping whoever falls off the end,
+    /// and returns the place counted from 1.
+    pub fn insert(&mut self, category: String, entry: Entry) -> Option<usize> {
+        let entries = self.boards.entry(category).or_default();
+        // Ties go to whoever got there first.
+        let place = entries.partition_point(|other| other.time <= entry.time);
+        entries.insert(place, entry);
+        entries.truncate(LEADERBOARD_SIZE);
+        (place < LEADERBOARD_SIZE).then_some(place + 1)
+    }
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / 86_400) as i64;
+    date_from_days(days)
+}
+
+/// The date `days` after 1970-01-01 as `YYYY-MM-DD`.
+fn date_from_days(days: i64) -> String {
+    // Civil date from a day count, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, time: f32) -> Entry {
+        Entry {
+            name: name.to_string(),
+            time,
+            date: "2024-01-01".to_string(),
+            bbbv_per_second: 1.0,
+            seed: 0,
+        }
+    }
+
+    fn names(leaderboard: &Leaderboard) -> Vec<&str> {
+        leaderboard
+            .entries("Base")
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn fastest_first_and_ties_keep_their_order() {
+        let mut leaderboard = Leaderboard::default();
+        assert_eq!(
+            leaderboard.insert("Base".to_string(), entry("b", 20.0)),
+            Some(1)
+        );
+        assert_eq!(
+            leaderboard.insert("Base".to_string(), entry("a", 10.0)),
+            Some(1)
+        );
+        assert_eq!(
+            leaderboard.insert("Base".to_string(), entry("c", 20.0)),
+            Some(3)
+        );
+        assert_eq!(
+            leaderboard.insert("Base".to_string(), entry("d", 15.0)),
+            Some(2)
+        );
+        assert_eq!(names(&leaderboard), ["a", "d", "b", "c"]);
+        assert!(leaderboard.entries("Expert").is_empty());
+    }
+
+    #[test]
+    fn only_the_top_ten_are_kept() {
+        let mut leaderboard = Leaderboard::default();
+        for i in 0..LEADERBOARD_SIZE {
+            assert!(leaderboard.qualifies("Base", 100.0));
+            leaderboard.insert("Base".to_string(), entry(&i.to_string(), 10.0 + i as f32));
+        }
+        // Matching the slowest time isn't enough once the board is full.
+        assert!(!leaderboard.qualifies("Base", 19.0));
+        assert!(leaderboard.qualifies("Base", 18.5));
+        assert_eq!(
+            leaderboard.insert("Base".to_string(), entry("late", 19.0)),
+            None
+        );
+        assert_eq!(
+            leaderboard.insert("Base".to_string(), entry("new", 1.0)),
+            Some(1)
+        );
+
+        let names = names(&leaderboard);
+        assert_eq!(names.len(), LEADERBOARD_SIZE);
+        assert_eq!(names.first(), Some(&"new"));
+        assert_eq!(names.last(), Some(&"8"));
+    }
+
+    #[test]
+    fn days_become_dates() {
+        assert_eq!(date_from_days(0), "1970-01-01");
+        assert_eq!(date_from_days(-1), "1969-12-31");
+        assert_eq!(date_from_days(11_016), "2000-02-29");
+        assert_eq!(date_from_days(19_723), "2024-01-01");
+        assert_eq!(date_from_days(47_541), "2100-03-01");
+    }
+}
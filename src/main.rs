@@ -1,53 +1,128 @@
+// Bevy systems take their parameters as arguments and queries spell out their filters.
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
 use bevy::{
+    app::AppExit,
     input::{mouse::MouseButtonInput, ButtonState},
     prelude::*,
     window::WindowResolution,
 };
 use bevy_ecs_tilemap::{
     map::{TilemapGridSize, TilemapSize, TilemapType},
-    tiles::{TilePos, TileTextureIndex},
+    tiles::TilePos,
     TilemapPlugin,
 };
 // use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use board::{board_resize, board_setup, TILE_SIZE};
+use board::{board_resize, board_setup, triangle_at, wrap_edge_system};
 use components::{
-    BombCount, GameButton, Level, LevelButton, MainButton, NumberIndex, StartTimeCount,
+    AntiMineButton, AntiMineText, BombCount, CustomButton, FirstClickButton, FirstClickText,
+    GameButton, HintButton, LeaderboardButton, Level, LevelButton, MainButton, MessageText,
+    MultiMineButton, MultiMineText, NeighbourhoodButton, NeighbourhoodText, NoGuessButton,
+    NoGuessText, NumberIndex, QuestionButton, QuestionText, SeedButton, SeedText, ShapeButton,
+    ShapeText, StartTimeCount, StatsButton, ThemedText, WrapButton, WrapText,
+};
+use custom::{custom_click_system, custom_dialog_system, custom_input_system};
+use engine::{FirstClick, Neighbourhood, Shape, Tile, MAX_MINES_PER_CELL};
+use hint::{hint_clear_system, hint_request_system};
+use history::{
+    history_commit_system, history_record_system, undo_redo_key_system, undo_redo_system,
+    HistoryEvent,
+};
+use over::{game_exit_system, game_lose_system, game_reset_system, game_win_system};
+use overlay::{probability_label_system, probability_overlay_system};
+use panel::{
+    leaderboard_panel_system, leaderboard_tab_click, metrics_panel_system, metrics_reset_system,
+    metrics_system, name_prompt_system, stats_panel_system,
+};
+use replay::{replay_control_system, replay_playback_system, replay_record_system};
+use resources::{
+    Board, BoardOptions, Clicks, CustomDialog, GameMetrics, Hint, History, LastStep, Leaderboard,
+    LeaderboardScreen, Message, NamePrompt, PendingLoad, Playback, ProbabilityOverlay, Recorder,
+    SeedInput, Settings, StartTime, Stats, StatsScreen,
 };
-use over::{game_lose_system, game_reset_system, game_win_system};
-use resources::{Board, BoardOptions, LastStep, StartTime, MARKED_INDEX};
+use save::{board_file_key_system, save_load_key_system};
+use settings::{camera_scale_system, load_settings, settings_watch_system, theme_system};
+use sound::sound_system;
 use tile::{
-    check_tiles_system, mark_tiles_system, number_tiles_system, re_uncover_tile_system,
-    safe_step_system, uncover_tiles_system, TileCheckEvent, TileMarkEvent, TileNumberEvent,
-    TileUncoverEvent,
+    count_label_system, mark_tiles_system, safe_step_system, sync_tiles_system, tile_color_system,
+    uncover_tiles_system, TileMarkEvent, TileUncoverEvent,
 };
 
 mod board;
+mod cli;
 mod components;
+mod custom;
+mod engine;
+mod format;
+mod hint;
+mod history;
+mod leaderboard;
+mod metrics;
 mod over;
+mod overlay;
+mod panel;
+mod replay;
 mod resources;
+mod save;
+mod settings;
+mod solver;
+mod sound;
+mod stats;
 mod tile;
+mod undo;
 
-const WINDOW_TOP_HEIGHT: f32 = 110.0;
+const WINDOW_TOP_HEIGHT: f32 = 170.0;
+const WINDOW_MIN_WIDTH: f32 = 560.0;
 const SELECT_LEVEL_HEIGHT: f32 = 30.0;
+const OPTION_BAR_HEIGHT: f32 = 20.0;
 const STATUS_BOX_HEIGHT: f32 = 50.0;
+const MESSAGE_HEIGHT: f32 = 20.0;
 const WINDOW_PADDING: f32 = 15.0;
+/// The default for `Settings::camera_scale`.
 const CAMERA_SCALE: f32 = 2.0;
 
 pub fn main() {
-    let window_width = (TILE_SIZE * BoardOptions::default().width as f32) * CAMERA_SCALE
-        + WINDOW_PADDING
-        + WINDOW_PADDING;
-    let window_height = (TILE_SIZE * BoardOptions::default().height as f32) * CAMERA_SCALE
+    let (settings, settings_watch) = load_settings();
+    let (board_options, save) = cli::from_env(settings.board_options());
+    let board_scale = settings.tile_size * settings.camera_scale;
+    let window_width = (board_scale * board_options.width as f32 + WINDOW_PADDING + WINDOW_PADDING)
+        .max(WINDOW_MIN_WIDTH);
+    let window_height = board_scale * board_options.height as f32
         + WINDOW_TOP_HEIGHT
         + WINDOW_PADDING
         + WINDOW_PADDING;
 
     App::new()
-        .insert_resource(ClearColor(Color::rgb_u8(204, 204, 204)))
-        .init_resource::<BoardOptions>()
+        .insert_resource(ClearColor(settings.theme.background()))
+        .insert_resource(board_options)
+        .insert_resource(settings)
+        .insert_resource(settings_watch)
         .init_resource::<Board>()
         .init_resource::<LastStep>()
         .init_resource::<StartTime>()
+        .init_resource::<SeedInput>()
+        .init_resource::<Hint>()
+        .init_resource::<History>()
+        .init_resource::<Recorder>()
+        .init_resource::<Playback>()
+        // The startup reset picks up a game loaded from the command line.
+        .insert_resource(PendingLoad(save))
+        .init_resource::<Clicks>()
+        .init_resource::<GameMetrics>()
+        .insert_resource(Stats::load().unwrap_or_else(|error| {
+            warn!("could not read stats, starting afresh: {}", error);
+            Stats::default()
+        }))
+        .init_resource::<StatsScreen>()
+        .insert_resource(Leaderboard::load().unwrap_or_else(|error| {
+            warn!("could not read the leaderboard, starting afresh: {}", error);
+            Leaderboard::default()
+        }))
+        .init_resource::<LeaderboardScreen>()
+        .init_resource::<NamePrompt>()
+        .init_resource::<CustomDialog>()
+        .init_resource::<Message>()
+        .init_resource::<ProbabilityOverlay>()
         .add_plugins(
             DefaultPlugins
                 .set(WindowPlugin {
@@ -63,12 +138,11 @@ pub fn main() {
         // .add_plugins(WorldInspectorPlugin::new())
         .add_plugins(TilemapPlugin)
         .add_event::<TileUncoverEvent>()
-        .add_event::<TileNumberEvent>()
-        .add_event::<TileCheckEvent>()
         .add_event::<TileMarkEvent>()
         .add_event::<GameWinEvent>()
         .add_event::<GameLoseEvent>()
         .add_event::<GameResetEvent>()
+        .add_event::<HistoryEvent>()
         .add_state::<GameState>()
         .add_systems(Startup, (game_setup, board_setup))
         .add_systems(
@@ -78,8 +152,46 @@ pub fn main() {
                 button_click_handler,
                 main_button_click,
                 level_button_click,
-                re_uncover_tile_system,
-                board_resize.run_if(resource_changed::<BoardOptions>()),
+                (
+                    custom_button_click,
+                    custom_input_system,
+                    custom_click_system,
+                    custom_dialog_system,
+                ),
+                stats_button_click,
+                leaderboard_button_click,
+                leaderboard_tab_click,
+                name_prompt_system,
+                (
+                    seed_button_click,
+                    seed_input_system,
+                    seed_text_system,
+                    first_click_button_click,
+                    first_click_text_system,
+                    no_guess_button_click,
+                    no_guess_text_system,
+                    shape_button_click,
+                    shape_text_system,
+                    wrap_button_click,
+                    wrap_text_system,
+                    neighbourhood_button_click,
+                    neighbourhood_text_system,
+                    multi_mine_button_click,
+                    multi_mine_text_system,
+                    anti_mine_button_click,
+                    anti_mine_text_system,
+                    question_button_click,
+                    question_text_system,
+                ),
+                hint_request_system
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(not_typing_name),
+                hint_clear_system,
+                undo_redo_key_system,
+                undo_redo_system.after(undo_redo_key_system),
+                message_text_system,
+                board_resize,
+                wrap_edge_system,
             ),
         )
         .add_systems(
@@ -88,11 +200,11 @@ pub fn main() {
                 game_timing,
                 game_start_timer,
                 game_bomb_count,
+                history_record_system,
                 safe_step_system,
                 uncover_tiles_system.after(safe_step_system),
-                number_tiles_system.after(safe_step_system),
-                check_tiles_system.after(safe_step_system),
                 mark_tiles_system.after(safe_step_system),
+                history_commit_system,
             )
                 .chain()
                 .distributive_run_if(in_state(GameState::InGame)),
@@ -101,10 +213,50 @@ pub fn main() {
             Update,
             (
                 game_lose_system.run_if(on_event::<GameLoseEvent>()),
-                game_win_system.run_if(on_event::<GameWinEvent>()),
+                game_win_system
+                    .run_if(on_event::<GameWinEvent>())
+                    .after(metrics_system),
                 game_reset_system.run_if(on_event::<GameResetEvent>()),
+                sync_tiles_system.run_if(resource_changed::<Board>()),
+                count_label_system,
+                metrics_system.run_if(on_event::<GameWinEvent>()),
+                metrics_reset_system.run_if(on_event::<GameResetEvent>()),
+                metrics_panel_system,
+                stats_panel_system,
+                leaderboard_panel_system,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                probability_overlay_system.run_if(not_typing_name),
+                probability_label_system.after(probability_overlay_system),
+                tile_color_system.after(probability_overlay_system),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                save_load_key_system,
+                board_file_key_system,
+                replay_control_system.run_if(not_typing_name),
+                replay_playback_system.after(replay_control_system),
+                replay_record_system
+                    .after(uncover_tiles_system)
+                    .after(mark_tiles_system)
+                    .after(undo_redo_system),
             ),
         )
+        .add_systems(
+            Update,
+            (
+                settings_watch_system,
+                theme_system.after(settings_watch_system),
+                camera_scale_system.after(settings_watch_system),
+                sound_system,
+            ),
+        )
+        .add_systems(Last, game_exit_system.run_if(on_event::<AppExit>()))
         .run()
 }
 
@@ -112,9 +264,10 @@ fn game_setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    settings: Res<Settings>,
 ) {
     let mut camera = Camera2dBundle::default();
-    camera.transform.scale /= CAMERA_SCALE;
+    camera.transform.scale /= settings.camera_scale;
     commands.spawn(camera);
 
     let box_image = asset_server.load::<Image>("box0.png");
@@ -266,6 +419,51 @@ fn game_setup(
         })
         .id();
 
+    let no_guess_button = text_button(&mut commands, "", NoGuessButton, NoGuessText);
+    let hint_button = text_button(&mut commands, "Hint", HintButton, ());
+    let custom_button = text_button(&mut commands, "Custom", CustomButton, ());
+    let stats_button = text_button(&mut commands, "Stats", StatsButton, ());
+    let leaderboard_button = text_button(&mut commands, "Scores", LeaderboardButton, ());
+
+    let ui_option = commands
+        .spawn(NodeBundle {
+            style: Style {
+                display: Display::Flex,
+                column_gap: Val::Px(15.0),
+                align_items: AlignItems::Center,
+                width: Val::Percent(100.0),
+                height: Val::Px(OPTION_BAR_HEIGHT),
+                ..default()
+            },
+            ..default()
+        })
+        .id();
+
+    let seed_button = text_button(&mut commands, "", SeedButton, SeedText);
+    let first_click_button = text_button(&mut commands, "", FirstClickButton, FirstClickText);
+    let shape_button = text_button(&mut commands, "", ShapeButton, ShapeText);
+    let question_button = text_button(&mut commands, "", QuestionButton, QuestionText);
+
+    let ui_variant = commands
+        .spawn(NodeBundle {
+            style: Style {
+                display: Display::Flex,
+                column_gap: Val::Px(15.0),
+                align_items: AlignItems::Center,
+                width: Val::Percent(100.0),
+                height: Val::Px(OPTION_BAR_HEIGHT),
+                ..default()
+            },
+            ..default()
+        })
+        .id();
+
+    let wrap_button = text_button(&mut commands, "", WrapButton, WrapText);
+    let neighbourhood_button =
+        text_button(&mut commands, "", NeighbourhoodButton, NeighbourhoodText);
+    let multi_mine_button = text_button(&mut commands, "", MultiMineButton, MultiMineText);
+    let anti_mine_button = text_button(&mut commands, "", AntiMineButton, AntiMineText);
+
     let ui_box = commands
         .spawn(NodeBundle {
             style: Style {
@@ -423,22 +621,98 @@ fn game_setup(
         })
         .id();
 
+    let ui_message = commands
+        .spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 11.0,
+                    color: Color::BLACK,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                width: Val::Percent(100.0),
+                height: Val::Px(MESSAGE_HEIGHT),
+                ..default()
+            }),
+            MessageText,
+            ThemedText,
+        ))
+        .id();
+
     commands
         .entity(ui_level)
         .add_child(level_base_button)
         .add_child(level_pro_button)
-        .add_child(level_expert_button);
+        .add_child(level_expert_button)
+        .add_child(custom_button)
+        .add_child(no_guess_button)
+        .add_child(hint_button)
+        .add_child(stats_button)
+        .add_child(leaderboard_button);
+    commands
+        .entity(ui_option)
+        .add_child(seed_button)
+        .add_child(first_click_button)
+        .add_child(shape_button)
+        .add_child(question_button);
+    commands
+        .entity(ui_variant)
+        .add_child(wrap_button)
+        .add_child(neighbourhood_button)
+        .add_child(multi_mine_button)
+        .add_child(anti_mine_button);
     commands
         .entity(ui_box)
         .add_child(ui_bomb)
         .add_child(ui_button)
         .add_child(ui_time);
     commands.entity(ui_body).add_child(ui_level);
+    commands.entity(ui_body).add_child(ui_option);
+    commands.entity(ui_body).add_child(ui_variant);
     commands.entity(ui_body).add_child(ui_box);
+    commands.entity(ui_body).add_child(ui_message);
+}
+
+fn text_button(
+    commands: &mut Commands,
+    label: &str,
+    button: impl Bundle,
+    text: impl Bundle,
+) -> Entity {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    align_items: AlignItems::Center,
+                    height: Val::Px(OPTION_BAR_HEIGHT),
+                    ..default()
+                },
+                ..default()
+            },
+            Interaction::default(),
+            button,
+        ))
+        .with_children(|children| {
+            children.spawn((
+                TextBundle::from_section(
+                    label,
+                    TextStyle {
+                        font_size: 12.0,
+                        color: Color::BLACK,
+                        ..default()
+                    },
+                ),
+                text,
+                ThemedText,
+            ));
+        })
+        .id()
 }
 
 fn game_timing(last_step: Res<LastStep>, time: Res<Time>, mut start_time: ResMut<StartTime>) {
-    if last_step.coord.is_some() && !last_step.uncover {
+    if last_step.coord.is_some() {
         start_time.0.tick(time.delta());
     }
 }
@@ -446,21 +720,44 @@ fn game_timing(last_step: Res<LastStep>, time: Res<Time>, mut start_time: ResMut
 fn cursor_movement(
     window: Query<&Window>,
     camera_query: Query<(&Camera, &GlobalTransform)>,
-    tilemap_query: Query<(&TilemapSize, &TilemapGridSize, &Transform)>,
+    tilemap_query: Query<(&TilemapSize, &TilemapGridSize, &TilemapType, &Transform)>,
     mut mouse_button_events: EventReader<MouseButtonInput>,
     mut tile_uncover_event: EventWriter<TileUncoverEvent>,
     mut tile_mark_event: EventWriter<TileMarkEvent>,
+    board_options: Res<BoardOptions>,
+    playback: Res<Playback>,
+    board: Res<Board>,
+    game_state: Res<State<GameState>>,
+    mut clicks: ResMut<Clicks>,
+    custom_dialog: Res<CustomDialog>,
+    interaction_query: Query<&Interaction>,
+    mut pressed_on_board: Local<bool>,
 ) {
+    // The board only takes the recorded moves while a replay is running.
+    if playback.is_active() {
+        mouse_button_events.clear();
+        return;
+    }
     let window = window.single();
     let (camera, camera_transfrom) = camera_query.single();
 
     for mouse_event in mouse_button_events.read() {
-        if mouse_event.state == ButtonState::Released {
+        // A click that starts on the UI or behind the dialog never reaches the board,
+        // even if the button under it is gone by the time it is released.
+        if mouse_event.state == ButtonState::Pressed {
+            *pressed_on_board = !custom_dialog.open
+                && interaction_query
+                    .iter()
+                    .all(|interaction| *interaction == Interaction::None);
+        }
+        if mouse_event.state == ButtonState::Released && *pressed_on_board {
             if let Some(cursor_pos) = window
                 .cursor_position()
                 .and_then(|cursor_pos| camera.viewport_to_world_2d(camera_transfrom, cursor_pos))
             {
-                for (tilemap_size, tilemap_grid_size, tilemap_transfrom) in tilemap_query.iter() {
+                for (tilemap_size, tilemap_grid_size, tilemap_type, tilemap_transfrom) in
+                    tilemap_query.iter()
+                {
                     let cursor_pos = {
                         let cursor_pos = Vec4::from((cursor_pos, 0.0, 1.0));
                         let cursor_in_map_pos =
@@ -468,14 +765,28 @@ fn cursor_movement(
                         cursor_in_map_pos.xy()
                     };
 
-                    if let Some(tile_pos) = TilePos::from_world_pos(
-                        &cursor_pos,
-                        tilemap_size,
-                        tilemap_grid_size,
-                        &TilemapType::Square,
-                    ) {
+                    let tile_pos = match board_options.shape {
+                        Shape::Triangle => triangle_at(cursor_pos, tilemap_size),
+                        _ => TilePos::from_world_pos(
+                            &cursor_pos,
+                            tilemap_size,
+                            tilemap_grid_size,
+                            tilemap_type,
+                        ),
+                    };
+                    if let Some(tile_pos) = tile_pos {
                         let coord = (tile_pos.x, tile_pos.y);
                         info!("cursor pos:{:?}", coord);
+                        if *game_state.get() == GameState::InGame {
+                            match mouse_event.button {
+                                MouseButton::Left if board.tile(coord) == Some(Tile::Opened) => {
+                                    clicks.chord += 1
+                                }
+                                MouseButton::Left => clicks.left += 1,
+                                MouseButton::Right => clicks.right += 1,
+                                _ => (),
+                            }
+                        }
                         match mouse_event.button {
                             MouseButton::Left => {
                                 tile_uncover_event.send(TileUncoverEvent { coord })
@@ -513,7 +824,7 @@ fn main_button_click(
 ) {
     for interaction in button_query.iter_mut() {
         if interaction == &Interaction::Pressed {
-            board_reset_event.send(GameResetEvent(board_options.clone()));
+            board_reset_event.send(GameResetEvent(board_options.clone().with_seed(None)));
         }
     }
 }
@@ -538,16 +849,13 @@ fn game_start_timer(
 }
 
 fn game_bomb_count(
-    texture_index_query: Query<&TileTextureIndex>,
     mut bomb_count_query: Query<(&mut UiTextureAtlasImage, &NumberIndex), With<BombCount>>,
     board_options: Res<BoardOptions>,
+    board: Res<Board>,
 ) {
-    let marked_count = texture_index_query
-        .iter()
-        .filter(|index| index.0 == MARKED_INDEX)
-        .count();
-
-    let num_int = board_options.bomb_count - marked_count as u32;
+    let num_int = board_options
+        .bomb_count
+        .saturating_sub(board.marked_count());
 
     let hundreds = ((num_int / 100) % 10) as usize;
     let tens = ((num_int / 10) % 10) as usize;
@@ -566,24 +874,420 @@ fn level_button_click(
     button_query: Query<(&Interaction, &Children), (With<LevelButton>, Changed<Interaction>)>,
     mut level_button_query: Query<&Level>,
     mut game_reset_event: EventWriter<GameResetEvent>,
+    current_options: Res<BoardOptions>,
 ) {
     for (interaction, children) in button_query.iter() {
         for child in children {
             if interaction == &Interaction::Pressed {
                 if let Ok(level) = level_button_query.get_mut(*child) {
-                    let board_options = match level {
-                        Level::Base => BoardOptions::new(9, 9, 10),
-                        Level::Pro => BoardOptions::new(16, 16, 40),
-                        Level::Expert => BoardOptions::new(30, 16, 99),
-                    };
+                    let (width, height, bomb_count) = level.size();
 
-                    game_reset_event.send(GameResetEvent(board_options));
+                    game_reset_event.send(GameResetEvent(BoardOptions {
+                        width,
+                        height,
+                        bomb_count,
+                        seed: None,
+                        ..current_options.clone()
+                    }));
                 }
             }
         }
     }
 }
 
+fn custom_button_click(
+    button_query: Query<&Interaction, (With<CustomButton>, Changed<Interaction>)>,
+    mut dialog: ResMut<CustomDialog>,
+    board_options: Res<BoardOptions>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            *dialog = CustomDialog {
+                open: true,
+                fields: [
+                    board_options.width.to_string(),
+                    board_options.height.to_string(),
+                    board_options.bomb_count.to_string(),
+                ],
+                ..default()
+            };
+        }
+    }
+}
+
+fn stats_button_click(
+    button_query: Query<&Interaction, (With<StatsButton>, Changed<Interaction>)>,
+    mut stats_screen: ResMut<StatsScreen>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            stats_screen.open = !stats_screen.open;
+        }
+    }
+}
+
+fn leaderboard_button_click(
+    button_query: Query<&Interaction, (With<LeaderboardButton>, Changed<Interaction>)>,
+    mut leaderboard_screen: ResMut<LeaderboardScreen>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            leaderboard_screen.open = !leaderboard_screen.open;
+        }
+    }
+}
+
+/// Plain letter shortcuts stay quiet while a name is being typed.
+fn not_typing_name(name_prompt: Res<NamePrompt>) -> bool {
+    !name_prompt.is_open()
+}
+
+fn seed_button_click(
+    button_query: Query<&Interaction, (With<SeedButton>, Changed<Interaction>)>,
+    mut seed_input: ResMut<SeedInput>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            seed_input.editing = true;
+            seed_input.text.clear();
+        }
+    }
+}
+
+fn seed_input_system(
+    mut char_events: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut seed_input: ResMut<SeedInput>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+    board_options: Res<BoardOptions>,
+) {
+    if !seed_input.editing {
+        char_events.clear();
+        return;
+    }
+
+    for event in char_events.read() {
+        if event.char.is_ascii_digit() && seed_input.text.len() < 20 {
+            seed_input.text.push(event.char);
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Back) {
+        seed_input.text.pop();
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        seed_input.editing = false;
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        seed_input.editing = false;
+        if let Ok(seed) = seed_input.text.parse::<u64>() {
+            game_reset_event.send(GameResetEvent(board_options.clone().with_seed(Some(seed))));
+        }
+    }
+}
+
+fn seed_text_system(
+    mut text_query: Query<&mut Text, With<SeedText>>,
+    seed_input: Res<SeedInput>,
+    board: Res<Board>,
+) {
+    if !seed_input.is_changed() && !board.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = if seed_input.editing {
+            format!("Seed: {}_", seed_input.text)
+        } else {
+            format!("Seed: {}", board.seed)
+        };
+    }
+}
+
+fn first_click_button_click(
+    button_query: Query<&Interaction, (With<FirstClickButton>, Changed<Interaction>)>,
+    mut board_options: ResMut<BoardOptions>,
+    mut board: ResMut<Board>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            board_options.first_click = match board_options.first_click {
+                FirstClick::Unprotected => FirstClick::Safe,
+                FirstClick::Safe => FirstClick::Opening,
+                FirstClick::Opening => FirstClick::Unprotected,
+            };
+            if !board.started {
+                board.options.first_click = board_options.first_click;
+            }
+        }
+    }
+}
+
+fn first_click_text_system(
+    mut text_query: Query<&mut Text, With<FirstClickText>>,
+    board_options: Res<BoardOptions>,
+) {
+    if !board_options.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = match board_options.first_click {
+            FirstClick::Unprotected => "First click: any",
+            FirstClick::Safe => "First click: safe",
+            FirstClick::Opening => "First click: opening",
+        }
+        .to_string();
+    }
+}
+
+fn no_guess_button_click(
+    button_query: Query<&Interaction, (With<NoGuessButton>, Changed<Interaction>)>,
+    mut board_options: ResMut<BoardOptions>,
+    mut board: ResMut<Board>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            board_options.no_guess = !board_options.no_guess;
+            if !board.started {
+                board.options.no_guess = board_options.no_guess;
+            }
+        }
+    }
+}
+
+fn no_guess_text_system(
+    mut text_query: Query<&mut Text, With<NoGuessText>>,
+    board_options: Res<BoardOptions>,
+) {
+    if !board_options.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = if board_options.no_guess {
+            "No guess: on".to_string()
+        } else {
+            "No guess: off".to_string()
+        };
+    }
+}
+
+fn shape_button_click(
+    button_query: Query<&Interaction, (With<ShapeButton>, Changed<Interaction>)>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+    current_options: Res<BoardOptions>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            let shape = match current_options.shape {
+                Shape::Square => Shape::Hex,
+                Shape::Hex => Shape::Triangle,
+                Shape::Triangle => Shape::Square,
+            };
+            game_reset_event.send(GameResetEvent(BoardOptions {
+                shape,
+                seed: None,
+                ..current_options.clone()
+            }));
+        }
+    }
+}
+
+fn shape_text_system(
+    mut text_query: Query<&mut Text, With<ShapeText>>,
+    board_options: Res<BoardOptions>,
+) {
+    if !board_options.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = match board_options.shape {
+            Shape::Square => "Shape: square",
+            Shape::Hex => "Shape: hex",
+            Shape::Triangle => "Shape: triangle",
+        }
+        .to_string();
+    }
+}
+
+fn wrap_button_click(
+    button_query: Query<&Interaction, (With<WrapButton>, Changed<Interaction>)>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+    current_options: Res<BoardOptions>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            game_reset_event.send(GameResetEvent(BoardOptions {
+                wrap: !current_options.wrap,
+                seed: None,
+                ..current_options.clone()
+            }));
+        }
+    }
+}
+
+fn wrap_text_system(
+    mut text_query: Query<&mut Text, With<WrapText>>,
+    board_options: Res<BoardOptions>,
+) {
+    if !board_options.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = match (board_options.wrap, board_options.wraps()) {
+            (false, _) => "Wrap: off",
+            (true, true) => "Wrap: on",
+            (true, false) => "Wrap: needs even size",
+        }
+        .to_string();
+    }
+}
+
+fn neighbourhood_button_click(
+    button_query: Query<&Interaction, (With<NeighbourhoodButton>, Changed<Interaction>)>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+    current_options: Res<BoardOptions>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            let neighbourhood = match current_options.neighbourhood {
+                Neighbourhood::Moore => Neighbourhood::Knight,
+                Neighbourhood::Knight => Neighbourhood::Cross,
+                Neighbourhood::Cross => Neighbourhood::Ring,
+                Neighbourhood::Ring => Neighbourhood::Moore,
+            };
+            game_reset_event.send(GameResetEvent(BoardOptions {
+                neighbourhood,
+                seed: None,
+                ..current_options.clone()
+            }));
+        }
+    }
+}
+
+fn neighbourhood_text_system(
+    mut text_query: Query<&mut Text, With<NeighbourhoodText>>,
+    board_options: Res<BoardOptions>,
+) {
+    if !board_options.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = match (board_options.shape, board_options.neighbourhood) {
+            (Shape::Hex | Shape::Triangle, _) => "Around: by shape",
+            (_, Neighbourhood::Moore) => "Around: 8",
+            (_, Neighbourhood::Knight) => "Around: knight",
+            (_, Neighbourhood::Cross) => "Around: cross",
+            (_, Neighbourhood::Ring) => "Around: ring",
+        }
+        .to_string();
+    }
+}
+
+fn multi_mine_button_click(
+    button_query: Query<&Interaction, (With<MultiMineButton>, Changed<Interaction>)>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+    current_options: Res<BoardOptions>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            game_reset_event.send(GameResetEvent(BoardOptions {
+                multi_mine: !current_options.multi_mine,
+                seed: None,
+                ..current_options.clone()
+            }));
+        }
+    }
+}
+
+fn multi_mine_text_system(
+    mut text_query: Query<&mut Text, With<MultiMineText>>,
+    board_options: Res<BoardOptions>,
+) {
+    if !board_options.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = if board_options.multi_mine {
+            format!("Mines/cell: 1-{}", MAX_MINES_PER_CELL)
+        } else {
+            "Mines/cell: 1".to_string()
+        };
+    }
+}
+
+fn anti_mine_button_click(
+    button_query: Query<&Interaction, (With<AntiMineButton>, Changed<Interaction>)>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+    current_options: Res<BoardOptions>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            let anti_mine_count = if current_options.anti_mine_count == 0 {
+                (current_options.bomb_count / 4).max(1)
+            } else {
+                0
+            };
+            game_reset_event.send(GameResetEvent(BoardOptions {
+                anti_mine_count,
+                seed: None,
+                ..current_options.clone()
+            }));
+        }
+    }
+}
+
+fn anti_mine_text_system(
+    mut text_query: Query<&mut Text, With<AntiMineText>>,
+    board_options: Res<BoardOptions>,
+) {
+    if !board_options.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = match board_options.anti_mine_count {
+            0 => "Anti-mines: off".to_string(),
+            count => format!("Anti-mines: {}", count),
+        };
+    }
+}
+
+fn question_button_click(
+    button_query: Query<&Interaction, (With<QuestionButton>, Changed<Interaction>)>,
+    mut board_options: ResMut<BoardOptions>,
+    mut board: ResMut<Board>,
+) {
+    for interaction in button_query.iter() {
+        if interaction == &Interaction::Pressed {
+            board_options.question_marks = !board_options.question_marks;
+            board.options.question_marks = board_options.question_marks;
+        }
+    }
+}
+
+fn question_text_system(
+    mut text_query: Query<&mut Text, With<QuestionText>>,
+    board_options: Res<BoardOptions>,
+) {
+    if !board_options.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = if board_options.question_marks {
+            "? marks: on".to_string()
+        } else {
+            "? marks: off".to_string()
+        };
+    }
+}
+
+fn message_text_system(mut text_query: Query<&mut Text, With<MessageText>>, message: Res<Message>) {
+    if !message.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = message.0.clone();
+    }
+}
+
 #[derive(Event)]
 struct GameWinEvent;
 
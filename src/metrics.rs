@@ -0,0 +1,244 @@
+use crate::engine::Board;
+
+/// Mouse clicks that reached the board during a game.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Clicks {
+    pub left: u32,
+    pub right: u32,
+    /// Left clicks on an opened number.
+    pub chord: u32,
+}
+
+impl Clicks {
+    pub fn total(&self) -> u32 {
+        self.left + self.right + self.chord
+    }
+}
+
+/// How a won game went, in the numbers players use to compare runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    /// Seconds from the first click to the win.
+    pub time: f32,
+    /// The fewest left clicks that clear the board without flags.
+    pub bbbv: u32,
+    /// The clicks a greedy player needs using flags and chords.
+    pub zini: u32,
+    pub clicks: Clicks,
+}
+
+impl Metrics {
+    pub fn new(board: &Board, time: f32, clicks: Clicks) -> Self {
+        Metrics {
+            time,
+            bbbv: bbbv(board),
+            zini: zini(board),
+            clicks,
+        }
+    }
+
+    pub fn bbbv_per_second(&self) -> f32 {
+        if self.time > 0.0 {
+            self.bbbv as f32 / self.time
+        } else {
+            0.0
+        }
+    }
+
+    /// Index of efficiency: 3BV per click.
+    pub fn ioe(&self) -> f32 {
+        match self.clicks.total() {
+            0 => 0.0,
+            clicks => self.bbbv as f32 / clicks as f32,
+        }
+    }
+
+    /// Rapidity quality product: time divided by 3BV/s, lower is better.
+    pub fn rqp(&self) -> f32 {
+        match self.bbbv_per_second() {
+            speed if speed > 0.0 => self.time / speed,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Replays the board's own flooding on a copy of what is open.
+struct Cover<'a> {
+    board: &'a Board,
+    opened: Vec<Vec<bool>>,
+    flagged: Vec<Vec<bool>>,
+}
+
+impl<'a> Cover<'a> {
+    fn new(board: &'a Board) -> Self {
+        let grid = board
+            .map
+            .iter()
+            .map(|column| vec![false; column.len()])
+            .collect::<Vec<_>>();
+        Cover {
+            board,
+            opened: grid.clone(),
+            flagged: grid,
+        }
+    }
+
+    fn is_opened(&self, (x, y): (u32, u32)) -> bool {
+        self.opened[x as usize][y as usize]
+    }
+
+    fn is_flagged(&self, (x, y): (u32, u32)) -> bool {
+        self.flagged[x as usize][y as usize]
+    }
+
+    /// A safe tile with no mine of any kind around it, so opening it floods.
+    fn is_opening(&self, coord: (u32, u32)) -> bool {
+        !self.board.is_bomb_at(coord)
+            && self
+                .board
+                .safe_square_at(coord)
+                .iter()
+                .all(|pos| !self.board.is_bomb_at(*pos))
+    }
+
+    fn open(&mut self, coord: (u32, u32)) {
+        let mut stack = vec![coord];
+        while let Some(pos) = stack.pop() {
+            if self.is_opened(pos) {
+                continue;
+            }
+            self.opened[pos.0 as usize][pos.1 as usize] = true;
+            if self.is_opening(pos) {
+                stack.extend(self.board.safe_square_at(pos));
+            }
+        }
+    }
+
+    /// Opens every opening with one click each, returning the clicks.
+    fn open_openings(&mut self) -> u32 {
+        let mut clicks = 0;
+        for coord in self.board.coords() {
+            if !self.is_opened(coord) && self.is_opening(coord) {
+                self.open(coord);
+                clicks += 1;
+            }
+        }
+        clicks
+    }
+
+    fn covered_safe(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.board
+            .coords()
+            .filter(|coord| !self.board.is_bomb_at(*coord) && !self.is_opened(*coord))
+    }
+}
+
+pub fn bbbv(board: &Board) -> u32 {
+    let mut cover = Cover::new(board);
+    let openings = cover.open_openings();
+    // What the openings leave needs one click per tile.
+    openings + cover.covered_safe().count() as u32
+}
+
+/// Greedy ZiNi: after the openings, keep chording wherever flagging and chording
+/// saves clicks over plain clicks, and click a single tile when nothing does.
+/// Every flag costs one click, as on a plain board.
+pub fn zini(board: &Board) -> u32 {
+    let mut cover = Cover::new(board);
+    let mut clicks = cover.open_openings();
+
+    loop {
+        let mut best: Option<(i32, (u32, u32))> = None;
+        for coord in board.coords() {
+            if board.is_bomb_at(coord) {
+                continue;
+            }
+            let around = board.safe_square_at(coord);
+            let gained = around
+                .iter()
+                .filter(|pos| !board.is_bomb_at(**pos) && !cover.is_opened(**pos))
+                .count() as i32;
+            let flags = around
+                .iter()
+                .filter(|pos| board.is_bomb_at(**pos) && !cover.is_flagged(**pos))
+                .count() as i32;
+            let premium = gained - flags - 1 - !cover.is_opened(coord) as i32;
+            if best.is_none_or(|(value, _)| premium > value) {
+                best = Some((premium, coord));
+            }
+        }
+
+        match best {
+            Some((premium, coord)) if premium > 0 => {
+                for pos in board.safe_square_at(coord) {
+                    if board.is_bomb_at(pos) && !cover.is_flagged(pos) {
+                        cover.flagged[pos.0 as usize][pos.1 as usize] = true;
+                        clicks += 1;
+                    }
+                }
+                if !cover.is_opened(coord) {
+                    cover.open(coord);
+                    clicks += 1;
+                }
+                for pos in board.safe_square_at(coord) {
+                    if !board.is_bomb_at(pos) {
+                        cover.open(pos);
+                    }
+                }
+                clicks += 1;
+            }
+            _ => {
+                let Some(coord) = cover.covered_safe().next() else {
+                    return clicks;
+                };
+                cover.open(coord);
+                clicks += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::parse_text;
+
+    #[test]
+    fn counts_openings_and_isolated_tiles() {
+        // One opening on the left, and a column on the right that only touches mines.
+        let board = parse_text("...*.\n...*.\n...*.\n").unwrap();
+        assert_eq!(bbbv(&board), 4);
+
+        let board = parse_text("*.*\n...\n*.*\n").unwrap();
+        assert_eq!(bbbv(&board), 5);
+    }
+
+    #[test]
+    fn zini_chords_only_when_it_saves_clicks() {
+        let board = parse_text("*.*\n...\n*.*\n").unwrap();
+        // Flagging four mines to chord the centre costs more than five plain clicks.
+        assert_eq!(zini(&board), 5);
+
+        // Flag the mine, open an edge and chord it, chord again, then one click.
+        let board = parse_text("...\n.*.\n...\n").unwrap();
+        assert_eq!(bbbv(&board), 8);
+        assert_eq!(zini(&board), 5);
+    }
+
+    #[test]
+    fn derived_numbers() {
+        let metrics = Metrics {
+            time: 10.0,
+            bbbv: 20,
+            zini: 15,
+            clicks: Clicks {
+                left: 20,
+                right: 3,
+                chord: 2,
+            },
+        };
+        assert_eq!(metrics.bbbv_per_second(), 2.0);
+        assert_eq!(metrics.ioe(), 0.8);
+        assert_eq!(metrics.rqp(), 5.0);
+    }
+}
@@ -2,77 +2,101 @@ use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
 
 use crate::{
-    components::GameButton,
+    board::{spawn_tilemap, spawn_tiles},
+    components::{GameButton, Level},
+    engine::GameStatus,
+    leaderboard::{today, Entry},
     resources::{
-        Board, BoardOptions, LastStep, StartTime, BOMB_INDEX, BOMB_RED_INDEX, MARKED_INDEX,
-        UNOPENED_INDEX,
+        Board, BoardOptions, GameMetrics, History, LastStep, Leaderboard, Message, NamePrompt,
+        PendingLoad, Playback, Recorder, StartTime, Stats,
     },
+    stats::category,
     GameResetEvent, GameState,
 };
 
+/// The face on the game button and the state that go with a board.
+pub fn face_and_state(status: GameStatus) -> (usize, GameState) {
+    match status {
+        GameStatus::Playing => (0, GameState::InGame),
+        GameStatus::Lost => (1, GameState::GameOver),
+        GameStatus::Won => (2, GameState::GameOver),
+    }
+}
+
 pub fn game_lose_system(
-    tile_pos_query: Query<&TilePos>,
-    tile_storage_query: Query<&TileStorage>,
-    mut tile_texture_index_query: Query<&mut TileTextureIndex>,
     mut button_query: Query<&mut UiTextureAtlasImage, With<GameButton>>,
-    board: Res<Board>,
-    last_step: Res<LastStep>,
     mut game_state: ResMut<NextState<GameState>>,
 ) {
-    let tile_storage = tile_storage_query.single();
-
     if let Ok(mut button_image) = button_query.get_single_mut() {
         button_image.index = 1;
     }
 
-    if let Some(coord) = last_step.coord {
-        let tile_pos = TilePos::new(coord.0, coord.1);
-        if let Some(tile_entity) = tile_storage.get(&tile_pos) {
-            if let Ok(mut texture_inedx) = tile_texture_index_query.get_mut(tile_entity) {
-                if texture_inedx.0 == BOMB_INDEX {
-                    texture_inedx.0 = BOMB_RED_INDEX;
-                }
-            }
-        }
-    }
-
-    for tile_pos in tile_pos_query.iter() {
-        let coord = (tile_pos.x, tile_pos.y);
-        if board.is_bomb_at(coord) {
-            if let Some(tile_entity) = tile_storage.get(tile_pos) {
-                if let Ok(mut texture_inedx) = tile_texture_index_query.get_mut(tile_entity) {
-                    if texture_inedx.0 == UNOPENED_INDEX {
-                        texture_inedx.0 = BOMB_INDEX;
-                    }
-                }
-            }
-        }
-    }
-
     game_state.set(GameState::GameOver);
 }
 
 pub fn game_win_system(
-    mut tile_texture_index_query: Query<&mut TileTextureIndex>,
     mut button_query: Query<&mut UiTextureAtlasImage, With<GameButton>>,
     mut game_state: ResMut<NextState<GameState>>,
+    board: Res<Board>,
+    start_time: Res<StartTime>,
+    playback: Res<Playback>,
+    leaderboard: Res<Leaderboard>,
+    game_metrics: Res<GameMetrics>,
+    mut name_prompt: ResMut<NamePrompt>,
 ) {
-    for mut texture_index in tile_texture_index_query.iter_mut() {
-        if texture_index.0 == UNOPENED_INDEX {
-            texture_index.0 = MARKED_INDEX;
-        }
-    }
     if let Ok(mut button_image) = button_query.get_single_mut() {
         button_image.index = 2;
     }
 
     game_state.set(GameState::GameOver);
+
+    // Only the standard levels have a leaderboard.
+    let time = start_time.0.elapsed_secs();
+    let category = category(&board.options);
+    if !playback.replayed
+        && !board.assisted
+        && Level::ALL.iter().any(|level| level.name() == category)
+        && leaderboard.qualifies(&category, time)
+    {
+        let entry = Entry {
+            name: String::new(),
+            time,
+            date: today(),
+            bbbv_per_second: game_metrics
+                .0
+                .as_ref()
+                .map_or(0.0, |metrics| metrics.bbbv_per_second()),
+            seed: board.seed,
+        };
+        name_prompt.pending = Some((category, entry));
+        name_prompt.text.clear();
+    }
+}
+
+/// Counts the game on screen once it can no longer be undone, which is when the
+/// next game starts or the app closes.
+fn record_game(board: &Board, start_time: &StartTime, playback: &Playback, stats: &mut Stats) {
+    if playback.replayed || board.status == GameStatus::Playing {
+        return;
+    }
+    stats.record_game(board, start_time.0.elapsed_secs());
+    if let Err(error) = stats.save() {
+        warn!("could not save stats: {}", error);
+    }
+}
+
+pub fn game_exit_system(
+    board: Res<Board>,
+    start_time: Res<StartTime>,
+    playback: Res<Playback>,
+    mut stats: ResMut<Stats>,
+) {
+    record_game(&board, &start_time, &playback, &mut stats);
 }
 
 pub fn game_reset_system(
     mut commands: Commands,
     mut tilemap_query: Query<(Entity, &mut TilemapSize, &mut TileStorage)>,
-    mut tile_texture_index_query: Query<&mut TileTextureIndex>,
     mut button_query: Query<&mut UiTextureAtlasImage, With<GameButton>>,
     mut game_reset_events: EventReader<GameResetEvent>,
     mut board: ResMut<Board>,
@@ -80,21 +104,54 @@ pub fn game_reset_system(
     mut start_time: ResMut<StartTime>,
     mut last_setp: ResMut<LastStep>,
     mut game_state: ResMut<NextState<GameState>>,
+    asset_server: Res<AssetServer>,
+    mut history: ResMut<History>,
+    mut playback: ResMut<Playback>,
+    mut recorder: ResMut<Recorder>,
+    mut pending_load: ResMut<PendingLoad>,
+    mut message: ResMut<Message>,
+    mut stats: ResMut<Stats>,
 ) {
+    // Options that can't make a board leave the current game as it is.
+    let resets = game_reset_events
+        .read()
+        .filter(|game_reset| match game_reset.0.validate() {
+            Ok(()) => true,
+            Err(error) => {
+                warn!("not starting a new board: {}", error);
+                message.0 = format!("Not a playable board: {}", error);
+                false
+            }
+        })
+        .collect::<Vec<_>>();
+    if resets.is_empty() {
+        pending_load.0 = None;
+        return;
+    }
+
+    record_game(&board, &start_time, &playback, &mut stats);
     if let Ok(mut button_image) = button_query.get_single_mut() {
         button_image.index = 0;
     }
 
-    if !last_setp.uncover {
-        last_setp.reset();
-    }
+    last_setp.reset();
+    history.clear();
 
-    for mut texture_index in tile_texture_index_query.iter_mut() {
-        texture_index.0 = UNOPENED_INDEX;
-    }
+    for game_reset in resets {
+        if board_options.shape != game_reset.0.shape {
+            // Each shape has its own tile size, grid and atlas, so the whole tilemap is rebuilt.
+            for (tilemap_entity, _, old_storage) in tilemap_query.iter() {
+                for entity in old_storage.iter().flatten() {
+                    commands.entity(*entity).despawn_recursive();
+                }
+                commands.entity(tilemap_entity).despawn_recursive();
+            }
+            spawn_tilemap(&mut commands, &asset_server, &game_reset.0);
 
-    for game_reset in game_reset_events.read() {
-        if board_options.width == game_reset.0.width && board_options.height == game_reset.0.height
+            board.reset(&game_reset.0);
+            *board_options = game_reset.0.clone();
+        } else if board_options.width == game_reset.0.width
+            && board_options.height == game_reset.0.height
         {
             board.reset(&game_reset.0);
             *board_options = game_reset.0.clone();
@@ -103,31 +160,13 @@ pub fn game_reset_system(
             if let Ok((tilemap_entity, mut map_size, mut old_storage)) =
                 tilemap_query.get_single_mut()
             {
-                let width = game_reset.0.width;
-                let height = game_reset.0.height;
-                let new_map_size = TilemapSize::new(width, height);
-                let mut new_storage = TileStorage::empty(new_map_size);
+                let new_map_size = TilemapSize::new(game_reset.0.width, game_reset.0.height);
 
                 for entity in old_storage.iter().flatten() {
                     commands.entity(*entity).despawn_recursive();
                 }
 
-                for x in 0..width {
-                    for y in 0..height {
-                        let tile_pos = TilePos::new(x, y);
-                        let tile_entity = commands
-                            .spawn(TileBundle {
-                                position: tile_pos,
-                                tilemap_id: TilemapId(tilemap_entity),
-                                texture_index: TileTextureIndex(UNOPENED_INDEX),
-                                ..default()
-                            })
-                            .id();
-                        new_storage.set(&tile_pos, tile_entity)
-                    }
-                }
-
-                *old_storage = new_storage;
+                *old_storage = spawn_tiles(&mut commands, tilemap_entity, new_map_size);
                 *map_size = new_map_size;
             } else {
                 println!("not storage");
@@ -138,6 +177,33 @@ pub fn game_reset_system(
         }
     }
 
+    // A replay starts from the recorded mines rather than a fresh layout, and a
+    // resumed game from the tiles that were open when it was saved.
+    let layout = playback.layout.take();
+    playback.replayed = layout.is_some();
+    if let Some(layout) = layout {
+        board.load_layout(layout);
+    }
+    if let Some(resumed) = playback.resumed.take() {
+        *board = resumed;
+    }
+
     start_time.0.reset();
-    game_state.set(GameState::InGame);
+
+    // A loaded game carries on from where it was saved.
+    recorder.resumed = None;
+    if let Some(save) = pending_load.0.take() {
+        recorder.resumed = Some(save.board.clone());
+        *board = save.board;
+        last_setp.coord = save.last_step;
+        start_time.0.set_elapsed(save.elapsed);
+    }
+    info!("seed: {}", board.seed);
+    debug!("board:\n{}", board.console_output());
+
+    let (face, state) = face_and_state(board.status);
+    if let Ok(mut button_image) = button_query.get_single_mut() {
+        button_image.index = face;
+    }
+    game_state.set(state);
 }
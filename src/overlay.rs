@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::{
+    components::ProbabilityLabel,
+    engine::{GameStatus, Tile},
+    resources::{Board, Message, ProbabilityOverlay, Settings},
+    solver,
+};
+
+pub fn probability_overlay_system(
+    keys: Res<Input<KeyCode>>,
+    board: Res<Board>,
+    mut overlay: ResMut<ProbabilityOverlay>,
+    mut message: ResMut<Message>,
+    settings: Res<Settings>,
+) {
+    let toggled = keys.just_pressed(settings.keys.overlay);
+    if toggled {
+        overlay.enabled = !overlay.enabled;
+    }
+    if !toggled && !board.is_changed() {
+        return;
+    }
+    if !overlay.enabled || board.status == GameStatus::Won {
+        if !overlay.values.is_empty() {
+            overlay.values.clear();
+        }
+        return;
+    }
+
+    // After a loss, show the chances the player was facing before the fatal click.
+    let mut view = board.clone();
+    if let Some((x, y)) = view.exploded {
+        view.tiles[x as usize][y as usize] = Tile::Unopened;
+    }
+    overlay.values = solver::probabilities(&view).unwrap_or_else(|| {
+        message.0 = "Mine chances are not available for this board".to_string();
+        vec![]
+    });
+}
+
+pub fn probability_label_system(
+    mut commands: Commands,
+    label_query: Query<Entity, With<ProbabilityLabel>>,
+    tilemap_query: Query<(Entity, &TilemapGridSize, &TilemapType)>,
+    overlay: Res<ProbabilityOverlay>,
+) {
+    if !overlay.is_changed() {
+        return;
+    }
+    for label in label_query.iter() {
+        commands.entity(label).despawn_recursive();
+    }
+
+    if let Ok((tilemap_entity, grid_size, map_type)) = tilemap_query.get_single() {
+        commands.entity(tilemap_entity).with_children(|children| {
+            for ((x, y), chance) in overlay.values.iter() {
+                let center = TilePos::new(*x, *y).center_in_world(grid_size, map_type);
+                children.spawn((
+                    Text2dBundle {
+                        text: Text::from_section(
+                            format!("{:.0}", chance * 100.0),
+                            TextStyle {
+                                font_size: 14.0,
+                                color: Color::rgb(0.2, 0.2, 0.2),
+                                ..default()
+                            },
+                        ),
+                        transform: Transform::from_translation(center.extend(1.0))
+                            .with_scale(Vec3::splat(0.5)),
+                        ..default()
+                    },
+                    ProbabilityLabel,
+                ));
+            }
+        });
+    }
+}
@@ -0,0 +1,299 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{LeaderboardPanel, LeaderboardTab, Level, MetricsPanel, StatsPanel},
+    engine::GameStatus,
+    resources::{
+        Board, Clicks, GameMetrics, History, Leaderboard, LeaderboardScreen, Message, Metrics,
+        NamePrompt, StartTime, Stats, StatsScreen,
+    },
+};
+
+const MAX_NAME_LENGTH: usize = 16;
+
+pub fn metrics_system(
+    board: Res<Board>,
+    start_time: Res<StartTime>,
+    clicks: Res<Clicks>,
+    mut game_metrics: ResMut<GameMetrics>,
+) {
+    game_metrics.0 = Some(Metrics::new(&board, start_time.0.elapsed_secs(), *clicks));
+}
+
+pub fn metrics_reset_system(mut clicks: ResMut<Clicks>, mut game_metrics: ResMut<GameMetrics>) {
+    *clicks = Clicks::default();
+    game_metrics.0 = None;
+}
+
+/// Shows the numbers over the board while the game stays won.
+pub fn metrics_panel_system(
+    mut commands: Commands,
+    panel_query: Query<Entity, With<MetricsPanel>>,
+    board: Res<Board>,
+    mut game_metrics: ResMut<GameMetrics>,
+) {
+    // Undoing the winning move takes the numbers away again.
+    if game_metrics.0.is_some() && board.status != GameStatus::Won {
+        game_metrics.0 = None;
+    }
+    if !game_metrics.is_changed() {
+        return;
+    }
+
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(metrics) = &game_metrics.0 else {
+        return;
+    };
+
+    let clicks = metrics.clicks;
+    let text = format!(
+        "Time {:.2}s\n3BV {}  3BV/s {:.2}\nZiNi {}\nClicks {} (left {}, right {}, chord {})\nIOE {:.3}  RQP {:.2}",
+        metrics.time,
+        metrics.bbbv,
+        metrics.bbbv_per_second(),
+        metrics.zini,
+        clicks.total(),
+        clicks.left,
+        clicks.right,
+        clicks.chord,
+        metrics.ioe(),
+        metrics.rqp(),
+    );
+    spawn_panel(&mut commands, MetricsPanel, |children| {
+        children.spawn(panel_text(text, Color::BLACK));
+    });
+}
+
+/// A box in the middle of the window, filled by `content`.
+pub fn spawn_panel(
+    commands: &mut Commands,
+    panel: impl Bundle,
+    content: impl FnOnce(&mut ChildBuilder),
+) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            panel,
+        ))
+        .with_children(|children| {
+            children
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(6.0),
+                        padding: UiRect::all(Val::Px(8.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(1.0, 1.0, 1.0, 0.9).into(),
+                    ..default()
+                })
+                .with_children(content);
+        });
+}
+
+pub fn panel_text(text: impl Into<String>, color: Color) -> TextBundle {
+    TextBundle::from_section(
+        text,
+        TextStyle {
+            font_size: 14.0,
+            color,
+            ..default()
+        },
+    )
+}
+
+/// The stats screen, rebuilt whenever it is toggled or a game is counted.
+pub fn stats_panel_system(
+    mut commands: Commands,
+    panel_query: Query<Entity, With<StatsPanel>>,
+    stats_screen: Res<StatsScreen>,
+    stats: Res<Stats>,
+) {
+    if !stats_screen.is_changed() && !stats.is_changed() {
+        return;
+    }
+
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !stats_screen.open {
+        return;
+    }
+
+    let seconds = |time: Option<f32>| time.map_or("-".to_string(), |time| format!("{:.2}s", time));
+    let mut lines = stats
+        .records
+        .iter()
+        .map(|(category, record)| {
+            format!(
+                "{}: played {}, won {} ({:.0}%), streak {} (best {}), best {}, average {}, assisted {}",
+                category,
+                record.played,
+                record.wins,
+                record.win_rate() * 100.0,
+                record.current_streak,
+                record.best_streak,
+                seconds(record.best_time),
+                seconds(record.average_time()),
+                record.assisted,
+            )
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        lines.push("No finished games yet".to_string());
+    }
+    spawn_panel(&mut commands, StatsPanel, |children| {
+        children.spawn(panel_text(lines.join("\n"), Color::BLACK));
+    });
+}
+
+/// Takes the name for a new record in the message line: Enter keeps it, Escape drops it.
+/// A kept record makes the win final, so it can no longer be undone.
+pub fn name_prompt_system(
+    mut char_events: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    board: Res<Board>,
+    mut history: ResMut<History>,
+    mut name_prompt: ResMut<NamePrompt>,
+    mut leaderboard: ResMut<Leaderboard>,
+    mut leaderboard_screen: ResMut<LeaderboardScreen>,
+    mut message: ResMut<Message>,
+) {
+    if !name_prompt.is_open() {
+        char_events.clear();
+        return;
+    }
+
+    for event in char_events.read() {
+        if !event.char.is_control() && name_prompt.text.chars().count() < MAX_NAME_LENGTH {
+            name_prompt.text.push(event.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        name_prompt.text.pop();
+    }
+
+    if board.status != GameStatus::Won || board.assisted {
+        name_prompt.pending = None;
+        message.0.clear();
+    } else if keys.just_pressed(KeyCode::Escape) {
+        name_prompt.pending = None;
+        message.0 = "Record not kept".to_string();
+    } else if keys.just_pressed(KeyCode::Return) {
+        let Some((category, mut entry)) = name_prompt.pending.take() else {
+            return;
+        };
+        entry.name = match name_prompt.text.trim() {
+            "" => "Anonymous".to_string(),
+            name => name.to_string(),
+        };
+        history.clear();
+        let place = leaderboard.insert(category.clone(), entry);
+        if let Err(error) = leaderboard.save() {
+            warn!("could not save the leaderboard: {}", error);
+        }
+        message.0 = match place {
+            Some(place) => format!("#{} on the {} leaderboard", place, category),
+            None => String::new(),
+        };
+
+        leaderboard_screen.open = true;
+        if let Some(tab) = Level::ALL.iter().position(|level| level.name() == category) {
+            leaderboard_screen.tab = tab;
+        }
+    } else {
+        let prompt = format!("New record! Your name: {}_", name_prompt.text);
+        if message.0 != prompt {
+            message.0 = prompt;
+        }
+    }
+}
+
+pub fn leaderboard_tab_click(
+    tab_query: Query<(&Interaction, &LeaderboardTab), Changed<Interaction>>,
+    mut leaderboard_screen: ResMut<LeaderboardScreen>,
+) {
+    for (interaction, tab) in tab_query.iter() {
+        if interaction == &Interaction::Pressed {
+            leaderboard_screen.tab = tab.0;
+        }
+    }
+}
+
+/// The leaderboard with one tab per level, rebuilt when it changes.
+pub fn leaderboard_panel_system(
+    mut commands: Commands,
+    panel_query: Query<Entity, With<LeaderboardPanel>>,
+    leaderboard_screen: Res<LeaderboardScreen>,
+    leaderboard: Res<Leaderboard>,
+) {
+    if !leaderboard_screen.is_changed() && !leaderboard.is_changed() {
+        return;
+    }
+
+    for entity in panel_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !leaderboard_screen.open {
+        return;
+    }
+
+    let level = &Level::ALL[leaderboard_screen.tab.min(Level::ALL.len() - 1)];
+    let mut lines = leaderboard
+        .entries(level.name())
+        .iter()
+        .enumerate()
+        .map(|(place, entry)| {
+            format!(
+                "{:>2}. {}  {:.2}s  {:.2} 3BV/s  {}  seed {}",
+                place + 1,
+                entry.name,
+                entry.time,
+                entry.bbbv_per_second,
+                entry.date,
+                entry.seed,
+            )
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        lines.push("No records yet".to_string());
+    }
+
+    spawn_panel(&mut commands, LeaderboardPanel, |children| {
+        children
+            .spawn(NodeBundle {
+                style: Style {
+                    column_gap: Val::Px(15.0),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|tabs| {
+                for (index, tab) in Level::ALL.iter().enumerate() {
+                    let color = if index == leaderboard_screen.tab {
+                        Color::BLACK
+                    } else {
+                        Color::GRAY
+                    };
+                    tabs.spawn((
+                        panel_text(tab.name(), color),
+                        Interaction::default(),
+                        LeaderboardTab(index),
+                    ));
+                }
+            });
+        children.spawn(panel_text(lines.join("\n"), Color::BLACK));
+    });
+}
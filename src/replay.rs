@@ -0,0 +1,198 @@
+use bevy::prelude::*;
+
+use crate::{
+    history::HistoryEvent,
+    resources::{Board, Message, MoveKind, Playback, RecordedMove, Recorder, Settings},
+    tile::{TileMarkEvent, TileUncoverEvent},
+    GameResetEvent, GameState,
+};
+
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 8.0;
+
+/// Appends the player's moves to the current recording.
+pub fn replay_record_system(
+    mut tile_uncover_events: EventReader<TileUncoverEvent>,
+    mut tile_mark_events: EventReader<TileMarkEvent>,
+    mut history_events: EventReader<HistoryEvent>,
+    mut game_reset_events: EventReader<GameResetEvent>,
+    board: Res<Board>,
+    playback: Res<Playback>,
+    game_state: Res<State<GameState>>,
+    time: Res<Time>,
+    mut recorder: ResMut<Recorder>,
+) {
+    if game_reset_events.read().count() > 0 && !recorder.current.moves.is_empty() {
+        recorder.last = Some(std::mem::take(&mut recorder.current));
+        recorder.started = None;
+    }
+    // The replay clicks first just like the game did, so it needs the mines as
+    // they were before the first click, including one that was undone.
+    if board.is_changed() && !board.started {
+        recorder.current.layout = board.map.clone();
+    }
+
+    let mut moves = Vec::new();
+    // Clicks after the game is over do nothing, so they are not part of the game.
+    if *game_state.get() == GameState::InGame {
+        moves.extend(
+            tile_uncover_events
+                .read()
+                .map(|event| (MoveKind::Uncover, event.coord)),
+        );
+        moves.extend(
+            tile_mark_events
+                .read()
+                .map(|event| (MoveKind::Mark, event.coord)),
+        );
+    } else {
+        tile_uncover_events.clear();
+        tile_mark_events.clear();
+    }
+    moves.extend(history_events.read().map(|event| match event {
+        HistoryEvent::Undo => (MoveKind::Undo, (0, 0)),
+        HistoryEvent::Redo => (MoveKind::Redo, (0, 0)),
+    }));
+
+    if moves.is_empty() || playback.is_active() {
+        return;
+    }
+
+    let now = time.elapsed_seconds();
+    let started = *recorder.started.get_or_insert(now);
+    if recorder.current.moves.is_empty() {
+        recorder.current.options = board.options.clone().with_seed(Some(board.seed));
+        recorder.current.resumed = recorder.resumed.clone();
+    }
+    recorder
+        .current
+        .moves
+        .extend(moves.into_iter().map(|(kind, coord)| RecordedMove {
+            time: now - started,
+            kind,
+            coord,
+        }));
+}
+
+/// R starts or stops a replay of the current game, or the previous one when
+/// nothing has been played yet. Space pauses, N steps one move, + and - change the speed.
+pub fn replay_control_system(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    recorder: Res<Recorder>,
+    mut playback: ResMut<Playback>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+    mut message: ResMut<Message>,
+) {
+    if keys.just_pressed(settings.keys.replay) {
+        if playback.is_active() {
+            playback.recording = None;
+            message.0 = "Replay stopped".to_string();
+            return;
+        }
+
+        let recording = if recorder.current.moves.is_empty() {
+            recorder.last.clone()
+        } else {
+            Some(recorder.current.clone())
+        };
+        let Some(recording) = recording else {
+            message.0 = "Nothing to replay yet".to_string();
+            return;
+        };
+
+        game_reset_event.send(GameResetEvent(recording.options.clone()));
+        *playback = Playback {
+            layout: Some(recording.layout.clone()),
+            resumed: recording.resumed.clone(),
+            recording: Some(recording),
+            speed: playback.speed,
+            ..default()
+        };
+        return;
+    }
+    if !playback.is_active() {
+        return;
+    }
+
+    if keys.just_pressed(settings.keys.replay_pause) {
+        playback.paused = !playback.paused;
+    }
+    if keys.just_pressed(settings.keys.replay_step) {
+        // Jump the clock to the next move so the playback system sends it.
+        playback.paused = true;
+        let next = playback.next;
+        if let Some(time) = playback
+            .recording
+            .as_ref()
+            .and_then(|recording| recording.moves.get(next))
+            .map(|next| next.time)
+        {
+            playback.clock = time;
+        }
+    }
+    if keys.any_just_pressed([KeyCode::Equals, KeyCode::NumpadAdd]) {
+        playback.speed = (playback.speed * 2.0).min(MAX_SPEED);
+    }
+    if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        playback.speed = (playback.speed / 2.0).max(MIN_SPEED);
+    }
+}
+
+/// Sends the recorded moves through the same events as the mouse, in real time.
+pub fn replay_playback_system(
+    time: Res<Time>,
+    mut playback: ResMut<Playback>,
+    mut tile_uncover_event: EventWriter<TileUncoverEvent>,
+    mut tile_mark_event: EventWriter<TileMarkEvent>,
+    mut history_event: EventWriter<HistoryEvent>,
+    mut message: ResMut<Message>,
+) {
+    // Wait until `game_reset_system` has loaded the recorded layout.
+    if !playback.is_active() || playback.layout.is_some() {
+        return;
+    }
+    if !playback.paused {
+        playback.clock += time.delta_seconds() * playback.speed;
+    }
+
+    let Playback {
+        recording,
+        next,
+        clock,
+        ..
+    } = &mut *playback;
+    let Some(recording) = recording else {
+        return;
+    };
+    while let Some(recorded) = recording.moves.get(*next) {
+        if recorded.time > *clock {
+            break;
+        }
+        let coord = recorded.coord;
+        match recorded.kind {
+            MoveKind::Uncover => tile_uncover_event.send(TileUncoverEvent { coord }),
+            MoveKind::Mark => tile_mark_event.send(TileMarkEvent { coord }),
+            MoveKind::Undo => history_event.send(HistoryEvent::Undo),
+            MoveKind::Redo => history_event.send(HistoryEvent::Redo),
+        }
+        *next += 1;
+    }
+
+    let total = recording.moves.len();
+    let status = if *next == total {
+        playback.recording = None;
+        "Replay finished".to_string()
+    } else {
+        format!(
+            "Replay {}/{} at {}x{}",
+            playback.next,
+            total,
+            playback.speed,
+            if playback.paused { ", paused" } else { "" }
+        )
+    };
+    if message.0 != status {
+        message.0 = status;
+    }
+}
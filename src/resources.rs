@@ -1,13 +1,33 @@
-use std::{time::Duration, vec};
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
 
 use bevy::prelude::*;
-use rand::{thread_rng, Rng};
 
-pub const OPENED_INDEX: u32 = 0;
+pub use crate::engine::{Board, BoardOptions};
+use crate::leaderboard::Entry;
+pub use crate::leaderboard::Leaderboard;
+pub use crate::metrics::{Clicks, Metrics};
+use crate::save::SaveFile;
+pub use crate::settings::Settings;
+pub use crate::stats::Stats;
+pub use crate::undo::History;
+
 pub const UNOPENED_INDEX: u32 = 9;
 pub const BOMB_INDEX: u32 = 10;
 pub const BOMB_RED_INDEX: u32 = 11;
 pub const MARKED_INDEX: u32 = 13;
+/// Numbers above 8 continue from here.
+pub const NUMBER_9_INDEX: u32 = 16;
+pub const ANTI_MARKED_INDEX: u32 = 24;
+pub const ANTI_MINE_INDEX: u32 = 25;
+pub const ANTI_MINE_RED_INDEX: u32 = 26;
+pub const QUESTION_INDEX: u32 = 27;
+/// Negative numbers go down from -1 here.
+pub const NEGATIVE_1_INDEX: u32 = 32;
+/// Down-pointing triangles sit after the up-pointing ones in `tri.png`.
+pub const TRIANGLE_DOWN_OFFSET: u32 = 48;
 
 #[derive(Resource)]
 pub struct StartTime(pub Timer);
@@ -21,170 +41,189 @@ impl Default for StartTime {
 #[derive(Resource, Default)]
 pub struct LastStep {
     pub coord: Option<(u32, u32)>,
-    pub uncover: bool,
 }
 
 impl LastStep {
     pub fn reset(&mut self) {
         self.coord = None;
-        self.uncover = false;
     }
 }
 
-#[derive(Resource, Debug, Hash, PartialEq, Eq, Clone)]
-pub struct BoardOptions {
-    pub width: u32,
-    pub height: u32,
-    pub bomb_count: u32,
+#[derive(Resource, Default)]
+pub struct SeedInput {
+    pub editing: bool,
+    pub text: String,
+}
+
+#[derive(Resource, Default)]
+pub struct Hint {
+    pub coord: Option<(u32, u32)>,
+    pub certain: bool,
+    pub mine: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct Message(pub String);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveKind {
+    Uncover,
+    Mark,
+    Undo,
+    Redo,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordedMove {
+    /// Seconds since the first move of the game.
+    pub time: f32,
+    pub kind: MoveKind,
+    pub coord: (u32, u32),
 }
 
-impl Default for BoardOptions {
+/// One game as played: its options (with the seed), the mines before the
+/// first click and every move.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    pub options: BoardOptions,
+    pub layout: Vec<Vec<i32>>,
+    /// The board the game was resumed from, with the tiles opened before the save.
+    pub resumed: Option<Board>,
+    pub moves: Vec<RecordedMove>,
+}
+
+#[derive(Resource, Default)]
+pub struct Recorder {
+    pub current: Recording,
+    /// Time of the first move of `current`.
+    pub started: Option<f32>,
+    /// The game before the current one.
+    pub last: Option<Recording>,
+    /// The loaded board the game on screen started from, if it was loaded.
+    pub resumed: Option<Board>,
+}
+
+#[derive(Resource)]
+pub struct Playback {
+    pub recording: Option<Recording>,
+    /// Index of the next move to play.
+    pub next: usize,
+    /// Replay time in seconds, on the same scale as `RecordedMove::time`.
+    pub clock: f32,
+    pub speed: f32,
+    pub paused: bool,
+    /// Layout for `game_reset_system` to load once the board is reset.
+    pub layout: Option<Vec<Vec<i32>>>,
+    /// Loaded instead of the layout when the recorded game was resumed from a save.
+    pub resumed: Option<Board>,
+    /// The board on screen came from a replay, so it isn't a real game.
+    pub replayed: bool,
+}
+
+impl Default for Playback {
     fn default() -> Self {
         Self {
-            width: 9,
-            height: 9,
-            bomb_count: 10,
+            recording: None,
+            next: 0,
+            clock: 0.0,
+            speed: 1.0,
+            paused: false,
+            layout: None,
+            resumed: None,
+            replayed: false,
         }
     }
 }
 
-impl BoardOptions {
-    pub fn new(w: u32, h: u32, b: u32) -> Self {
-        BoardOptions {
-            width: w,
-            height: h,
-            bomb_count: b,
-        }
-    }
-    pub fn area(&self) -> u32 {
-        self.width * self.height
+impl Playback {
+    pub fn is_active(&self) -> bool {
+        self.recording.is_some()
     }
 }
 
+/// A loaded save for `game_reset_system` to restore once the tilemap is rebuilt.
 #[derive(Resource, Default)]
-pub struct Board {
-    pub map: Vec<Vec<u32>>,
-}
-
-const SQUARE_COORD: [(i8, i8); 8] = [
-    // Top Left
-    (-1, 1),
-    // Top
-    (0, 1),
-    // Top right
-    (1, 1),
-    // Left
-    (-1, 0),
-    // Right
-    (1, 0),
-    // Bottom left
-    (-1, -1),
-    // Bottom
-    (0, -1),
-    // Bottom right
-    (1, -1),
-];
-
-impl Board {
-    pub fn reset(&mut self, options: &BoardOptions) {
-        let mut rng = thread_rng();
-        let mut map = (0..options.area())
-            .map(|i| {
-                if i < options.bomb_count {
-                    BOMB_INDEX
-                } else {
-                    OPENED_INDEX
-                }
-            })
-            .collect::<Vec<u32>>();
-
-        for i in 0..options.area() {
-            let index = i as usize;
-            let random = rng.gen_range(0..options.area()) as usize;
-            if map[index] != map[random] {
-                map[index] = OPENED_INDEX;
-                map[random] = BOMB_INDEX;
-            }
-        }
+pub struct PendingLoad(pub Option<SaveFile>);
 
-        self.map = map
-            .chunks(options.height as usize)
-            .map(|i| i.to_vec())
-            .collect();
+#[derive(Resource, Default)]
+pub struct ProbabilityOverlay {
+    pub enabled: bool,
+    pub values: Vec<((u32, u32), f64)>,
+}
 
-        println!("{}", self.console_output());
-    }
+impl Resource for BoardOptions {}
 
-    fn width(&self) -> usize {
-        self.map[0].len()
-    }
+impl Resource for Board {}
 
-    fn height(&self) -> usize {
-        self.map.len()
-    }
+impl Resource for Clicks {}
 
-    pub fn get(&self, coord: (u32, u32)) -> u32 {
-        let (x, y) = (coord.0 as usize, coord.1 as usize);
-        if let Some(x_val) = self.map.get(x) {
-            if let Some(y_val) = x_val.get(y) {
-                return *y_val;
-            }
-        }
-        0
-    }
+impl Resource for Stats {}
 
-    pub fn is_bomb_at(&self, coord: (u32, u32)) -> bool {
-        if self.get(coord) == BOMB_INDEX {
-            return true;
-        }
-        false
-    }
+/// The custom board dialog: width, height and mines as typed.
+#[derive(Resource, Default)]
+pub struct CustomDialog {
+    pub open: bool,
+    pub fields: [String; 3],
+    /// Index of the field taking input.
+    pub field: usize,
+    pub error: Option<String>,
+}
 
-    pub fn bomb_count_at(&self, coord: (u32, u32)) -> u32 {
-        if self.is_bomb_at(coord) {
-            return 0;
-        }
-        let res = self
-            .safe_square_at(coord)
-            .into_iter()
-            .filter(|coord| self.is_bomb_at(*coord))
-            .count();
-        res as u32
-    }
+#[derive(Resource, Default)]
+pub struct StatsScreen {
+    pub open: bool,
+}
+
+impl Resource for Leaderboard {}
+
+#[derive(Resource, Default)]
+pub struct LeaderboardScreen {
+    pub open: bool,
+    /// Index into `Level::ALL`.
+    pub tab: usize,
+}
+
+/// A winning time that made the leaderboard, waiting for the player's name.
+#[derive(Resource, Default)]
+pub struct NamePrompt {
+    pub pending: Option<(String, Entry)>,
+    pub text: String,
+}
 
-    pub fn safe_square_at(&self, coord: (u32, u32)) -> Vec<(u32, u32)> {
-        SQUARE_COORD
-            .iter()
-            .copied()
-            .map(|tuple| {
-                (
-                    (coord.0 as i32 + tuple.0 as i32),
-                    (coord.1 as i32 + tuple.1 as i32),
-                )
-            })
-            .filter(|coord| {
-                coord.0 >= 0
-                    && coord.1 >= 0
-                    && coord.0 < (self.map.len() as i32)
-                    && coord.1 < (self.map.len() as i32)
-            })
-            .map(|coord| (coord.0 as u32, coord.1 as u32))
-            .collect()
+impl NamePrompt {
+    pub fn is_open(&self) -> bool {
+        self.pending.is_some()
     }
+}
+
+/// The numbers of the game just won, until the next game starts.
+#[derive(Resource, Default)]
+pub struct GameMetrics(pub Option<Metrics>);
+
+impl Resource for Settings {}
+
+impl Resource for History {}
+
+/// How often the settings file is checked for edits.
+const SETTINGS_POLL_SECONDS: f32 = 1.0;
+
+/// The settings file and when it last changed.
+#[derive(Resource)]
+pub struct SettingsWatch {
+    pub path: Option<PathBuf>,
+    pub modified: Option<SystemTime>,
+    pub timer: Timer,
+    /// Found before logging started, so they are logged on the first frame.
+    pub warnings: Vec<String>,
+}
 
-    pub fn console_output(&self) -> String {
-        let separator: String = (0..=self.height() * 3).map(|_| '-').collect();
-        let mut board = vec![];
-
-        for i in 0..self.width() {
-            let mut row = vec![];
-            for j in 0..self.height() {
-                let column = self.map[j][i];
-                row.push(format!("{:2}", column));
-            }
-            board.push(format!("|{}|", row.join(" ")));
+impl SettingsWatch {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        SettingsWatch {
+            path,
+            modified: None,
+            timer: Timer::from_seconds(SETTINGS_POLL_SECONDS, TimerMode::Repeating),
+            warnings: vec![],
         }
-        board.reverse();
-        format!("{}\n{}\n{}", separator, board.join("\n"), separator)
     }
 }
@@ -0,0 +1,471 @@
+use std::{fmt, fs, io, path::Path, time::Duration};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    engine::{GameStatus, OptionsError},
+    format::{self, ParseError},
+    resources::{Board, LastStep, Message, PendingLoad, Playback, Settings, StartTime},
+    tile::texture_index_at,
+    GameResetEvent,
+};
+
+pub const SAVE_PATH: &str = "minesweeper-save.ron";
+pub const BOARD_TEXT_PATH: &str = "board.txt";
+pub const BOARD_MBF_PATH: &str = "board.mbf";
+
+/// Bumped whenever a change to the file can't be read by the old code.
+pub const SAVE_VERSION: u32 = 1;
+
+/// Everything needed to carry on a game where it was left.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub version: u32,
+    pub board: Board,
+    /// Texture index of every tile, indexed like `Board::map`.
+    pub textures: Vec<Vec<u32>>,
+    pub last_step: Option<(u32, u32)>,
+    pub elapsed: Duration,
+}
+
+/// Only the version, read first so a file from another version gets a clear error
+/// instead of a confusing one about some field.
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Format(ron::Error),
+    Parse(ron::error::SpannedError),
+    /// Any version other than `SAVE_VERSION`, which is the only one this build reads.
+    UnsupportedVersion(u32),
+    /// The tiles don't match the board they were saved with.
+    Corrupt(String),
+    /// The saved options can't make a board.
+    Options(OptionsError),
+    /// A board file that isn't valid text or MBF.
+    Board(ParseError),
+    /// The board can't be written in the chosen board format.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{}", error),
+            SaveError::Format(error) => write!(f, "{}", error),
+            SaveError::Parse(error) => write!(f, "{}", error),
+            SaveError::UnsupportedVersion(version) => write!(
+                f,
+                "save version {} is not supported, this build only reads version {}",
+                version, SAVE_VERSION
+            ),
+            SaveError::Corrupt(reason) => write!(f, "save is damaged: {}", reason),
+            SaveError::Options(error) => write!(f, "save is damaged: {}", error),
+            SaveError::Board(error) => write!(f, "{}", error),
+            SaveError::Unsupported(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(error: ron::Error) -> Self {
+        SaveError::Format(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for SaveError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        SaveError::Parse(error)
+    }
+}
+
+impl From<OptionsError> for SaveError {
+    fn from(error: OptionsError) -> Self {
+        SaveError::Options(error)
+    }
+}
+
+impl From<ParseError> for SaveError {
+    fn from(error: ParseError) -> Self {
+        SaveError::Board(error)
+    }
+}
+
+impl SaveFile {
+    /// A fresh start on `board`, as for an imported board.
+    pub fn from_board(mut board: Board) -> Self {
+        mark_restored(&mut board);
+        let textures = board
+            .map
+            .iter()
+            .enumerate()
+            .map(|(x, column)| {
+                (0..column.len())
+                    .map(|y| texture_index_at(&board, (x as u32, y as u32)))
+                    .collect()
+            })
+            .collect();
+        SaveFile {
+            version: SAVE_VERSION,
+            board,
+            textures,
+            last_step: None,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String, SaveError> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default().compact_arrays(true),
+        )?)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, SaveError> {
+        let header: SaveHeader = ron::from_str(text)?;
+        // Version 1 is the only format so far; older versions would be migrated here.
+        if header.version != SAVE_VERSION {
+            return Err(SaveError::UnsupportedVersion(header.version));
+        }
+
+        let mut save: SaveFile = ron::from_str(text)?;
+        save.validate()?;
+        mark_restored(&mut save.board);
+        Ok(save)
+    }
+
+    fn validate(&self) -> Result<(), SaveError> {
+        let board = &self.board;
+        board.options.validate()?;
+        let (width, height) = (board.options.width as usize, board.options.height as usize);
+        if !is_grid(&board.map, width, height)
+            || !is_grid(&board.tiles, width, height)
+            || !is_grid(&board.flags, width, height)
+            || !is_grid(&self.textures, width, height)
+        {
+            return Err(SaveError::Corrupt(format!(
+                "the board is not {}x{}",
+                width, height
+            )));
+        }
+
+        let on_board = |(x, y): (u32, u32)| (x as usize) < width && (y as usize) < height;
+        if let Some(coord) = board.exploded.filter(|coord| !on_board(*coord)) {
+            return Err(SaveError::Corrupt(format!(
+                "exploded tile {:?} is off the board",
+                coord
+            )));
+        }
+        if let Some(coord) = self.last_step.filter(|coord| !on_board(*coord)) {
+            return Err(SaveError::Corrupt(format!(
+                "last step {:?} is off the board",
+                coord
+            )));
+        }
+
+        if let Some((x, y)) = board.coords().find(|&(x, y)| {
+            texture_index_at(board, (x, y)) != self.textures[x as usize][y as usize]
+        }) {
+            return Err(SaveError::Corrupt(format!(
+                "tile ({}, {}) does not match the board",
+                x, y
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A game that was already over when it was saved or exported has been counted.
+fn mark_restored(board: &mut Board) {
+    board.restored = board.status != GameStatus::Playing;
+}
+
+fn is_grid<T>(grid: &[Vec<T>], width: usize, height: usize) -> bool {
+    grid.len() == width && grid.iter().all(|column| column.len() == height)
+}
+
+fn save_game(save: &SaveFile) -> Result<(), SaveError> {
+    fs::write(SAVE_PATH, save.to_ron()?)?;
+    Ok(())
+}
+
+fn load_game() -> Result<SaveFile, SaveError> {
+    read_game_file(Path::new(SAVE_PATH))
+}
+
+/// A saved game from a `.ron` file, otherwise a fresh start on a board file.
+pub fn read_game_file(path: &Path) -> Result<SaveFile, SaveError> {
+    let is_save = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ron"));
+    if is_save {
+        SaveFile::from_ron(&fs::read_to_string(path)?)
+    } else {
+        Ok(SaveFile::from_board(read_board_file(path)?))
+    }
+}
+
+/// Reads a data file, treating a missing one as empty.
+pub fn read_ron<T: DeserializeOwned + Default>(path: &str) -> Result<T, SaveError> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(ron::from_str(&text)?),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+pub fn write_ron<T: Serialize>(path: &str, value: &T) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+    fs::write(path, text)?;
+    Ok(())
+}
+
+fn is_mbf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("mbf"))
+}
+
+/// Reads a board as MBF when the file ends in `.mbf` and as a text grid otherwise.
+pub fn read_board_file(path: &Path) -> Result<Board, SaveError> {
+    if is_mbf(path) {
+        Ok(format::parse_mbf(&fs::read(path)?)?)
+    } else {
+        Ok(format::parse_text(&fs::read_to_string(path)?)?)
+    }
+}
+
+pub fn write_board_file(path: &Path, board: &Board) -> Result<(), SaveError> {
+    if is_mbf(path) {
+        let data = format::to_mbf(board).ok_or(SaveError::Unsupported(
+            "MBF only holds plain boards up to 255x255",
+        ))?;
+        fs::write(path, data)?;
+    } else {
+        let text = format::to_text(board)
+            .ok_or(SaveError::Unsupported("text boards only hold plain mines"))?;
+        fs::write(path, text)?;
+    }
+    Ok(())
+}
+
+/// Ctrl+S writes the game to `SAVE_PATH` and Ctrl+O picks it up again.
+pub fn save_load_key_system(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    tile_storage_query: Query<&TileStorage>,
+    tile_query: Query<(&TilePos, &TileTextureIndex)>,
+    board: Res<Board>,
+    last_step: Res<LastStep>,
+    start_time: Res<StartTime>,
+    playback: Res<Playback>,
+    mut pending_load: ResMut<PendingLoad>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+    mut message: ResMut<Message>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || playback.is_active() {
+        return;
+    }
+
+    if keys.just_pressed(settings.keys.save) {
+        let mut textures = board
+            .map
+            .iter()
+            .map(|column| vec![0; column.len()])
+            .collect::<Vec<_>>();
+        for tile_entity in tile_storage_query.iter().flat_map(|storage| storage.iter()) {
+            if let Some((tile_pos, texture_index)) =
+                tile_entity.and_then(|entity| tile_query.get(entity).ok())
+            {
+                textures[tile_pos.x as usize][tile_pos.y as usize] = texture_index.0;
+            }
+        }
+
+        let save = SaveFile {
+            version: SAVE_VERSION,
+            board: board.clone(),
+            textures,
+            last_step: last_step.coord,
+            elapsed: start_time.0.elapsed(),
+        };
+        message.0 = match save_game(&save) {
+            Ok(()) => format!("Saved to {}", SAVE_PATH),
+            Err(error) => format!("Could not save: {}", error),
+        };
+    } else if keys.just_pressed(settings.keys.load) {
+        match load_game() {
+            Ok(save) => {
+                // The reset rebuilds the tilemap for the saved options, then takes the save.
+                game_reset_event.send(GameResetEvent(save.board.options.clone()));
+                pending_load.0 = Some(save);
+                message.0 = format!("Loaded {}", SAVE_PATH);
+            }
+            Err(error) => message.0 = format!("Could not load: {}", error),
+        }
+    }
+}
+
+/// Ctrl+E writes the board to `BOARD_TEXT_PATH` and `BOARD_MBF_PATH`,
+/// Ctrl+I plays the text one and Ctrl+B the MBF one.
+pub fn board_file_key_system(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    board: Res<Board>,
+    playback: Res<Playback>,
+    mut pending_load: ResMut<PendingLoad>,
+    mut game_reset_event: EventWriter<GameResetEvent>,
+    mut message: ResMut<Message>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || playback.is_active() {
+        return;
+    }
+
+    if keys.just_pressed(settings.keys.export) {
+        let written = write_board_file(Path::new(BOARD_TEXT_PATH), &board)
+            .and_then(|()| write_board_file(Path::new(BOARD_MBF_PATH), &board));
+        message.0 = match written {
+            Ok(()) => format!("Exported {} and {}", BOARD_TEXT_PATH, BOARD_MBF_PATH),
+            Err(error) => format!("Could not export: {}", error),
+        };
+        return;
+    }
+
+    let path = if keys.just_pressed(settings.keys.import_text) {
+        BOARD_TEXT_PATH
+    } else if keys.just_pressed(settings.keys.import_mbf) {
+        BOARD_MBF_PATH
+    } else {
+        return;
+    };
+    match read_board_file(Path::new(path)) {
+        Ok(imported) => {
+            game_reset_event.send(GameResetEvent(imported.options.clone()));
+            pending_load.0 = Some(SaveFile::from_board(imported));
+            message.0 = format!("Imported {}", path);
+        }
+        Err(error) => message.0 = format!("Could not import {}: {}", path, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{engine::BoardOptions, stats::Stats};
+
+    fn played_save() -> SaveFile {
+        let mut board = Board::default();
+        board.reset(&BoardOptions::new(9, 9, 10).with_seed(Some(5)));
+        board.uncover((4, 4));
+        board.mark((0, 0));
+        SaveFile {
+            last_step: Some((4, 4)),
+            elapsed: Duration::from_millis(12_345),
+            ..SaveFile::from_board(board)
+        }
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let save = played_save();
+        let text = save.to_ron().unwrap();
+        let loaded = SaveFile::from_ron(&text).unwrap();
+
+        assert_eq!(loaded.board, save.board);
+        assert_eq!(loaded.textures, save.textures);
+        assert_eq!(loaded.last_step, Some((4, 4)));
+        assert_eq!(loaded.elapsed, Duration::from_millis(12_345));
+        assert_eq!(loaded.to_ron().unwrap(), text);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        for version in [0, SAVE_VERSION + 1] {
+            let save = SaveFile {
+                version,
+                ..played_save()
+            };
+            let error = SaveFile::from_ron(&save.to_ron().unwrap()).unwrap_err();
+            assert!(matches!(error, SaveError::UnsupportedVersion(v) if v == version));
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "save version {} is not supported, this build only reads version 1",
+                    version
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn positions_must_be_on_the_board() {
+        let mut save = played_save();
+        save.last_step = Some((9, 0));
+        let error = SaveFile::from_ron(&save.to_ron().unwrap()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "save is damaged: last step (9, 0) is off the board"
+        );
+
+        let mut save = played_save();
+        save.board.exploded = Some((0, 20));
+        let error = SaveFile::from_ron(&save.to_ron().unwrap()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "save is damaged: exploded tile (0, 20) is off the board"
+        );
+    }
+
+    #[test]
+    fn finished_games_are_not_counted_again_when_loaded() {
+        let mut board = Board::default();
+        board.reset(&BoardOptions::new(9, 9, 10).with_seed(Some(5)));
+        board.uncover((4, 4));
+        let mine = board
+            .coords()
+            .find(|coord| board.is_bomb_at(*coord))
+            .unwrap();
+        board.uncover(mine);
+        assert_eq!(board.status, GameStatus::Lost);
+
+        // The loss is counted when the next game starts.
+        let mut stats = Stats::default();
+        stats.record_game(&board, 1.0);
+        let counted = stats.clone();
+
+        // Saving the lost game and loading it again doesn't count it a second time.
+        let text = SaveFile::from_board(board.clone()).to_ron().unwrap();
+        let loaded = SaveFile::from_ron(&text).unwrap();
+        assert_eq!(loaded.board.status, GameStatus::Lost);
+        stats.record_game(&loaded.board, 1.0);
+        assert_eq!(stats, counted);
+
+        // Nor does importing a board exported with its mine hit.
+        let imported = format::parse_text("X.\n..\n").unwrap();
+        stats.record_game(&SaveFile::from_board(imported).board, 1.0);
+        assert_eq!(stats, counted);
+
+        // A game loaded while still being played counts once it is over.
+        let mut loaded = SaveFile::from_ron(&played_save().to_ron().unwrap()).unwrap();
+        let mine = loaded
+            .board
+            .coords()
+            .find(|coord| loaded.board.is_bomb_at(*coord))
+            .unwrap();
+        loaded.board.uncover(mine);
+        stats.record_game(&loaded.board, 1.0);
+        assert_ne!(stats, counted);
+    }
+}
@@ -0,0 +1,720 @@
+//! The user's settings file, `settings.ron` in the platform config directory.
+//! Every entry is optional, and one that can't be read is reported and left at
+//! its default instead of stopping the game. Edits are picked up while it runs.
+
+use std::{
+    env, fmt, fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    board::TILE_SIZE,
+    components::{Level, ThemedText},
+    engine::FirstClick,
+    resources::{BoardOptions, Message, SettingsWatch},
+    CAMERA_SCALE,
+};
+
+const CONFIG_DIR: &str = "bevy-minesweeper";
+const SETTINGS_FILE: &str = "settings.ron";
+
+const CAMERA_SCALE_RANGE: RangeInclusive<f32> = 1.0..=4.0;
+const TILE_SIZE_RANGE: RangeInclusive<f32> = 8.0..=64.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn background(&self) -> Color {
+        match self {
+            Theme::Light => Color::rgb_u8(204, 204, 204),
+            Theme::Dark => Color::rgb_u8(48, 48, 48),
+        }
+    }
+
+    pub fn text(&self) -> Color {
+        match self {
+            Theme::Light => Color::BLACK,
+            Theme::Dark => Color::rgb_u8(224, 224, 224),
+        }
+    }
+}
+
+/// Keys for the shortcuts, which the settings file can rebind; the systems' docs name
+/// the defaults. Undo, redo, saving and board files also need Ctrl. The replay speed
+/// keys, + and -, can't be rebound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBindings {
+    pub hint: KeyCode,
+    pub overlay: KeyCode,
+    pub undo: KeyCode,
+    pub redo: KeyCode,
+    pub save: KeyCode,
+    pub load: KeyCode,
+    pub export: KeyCode,
+    pub import_text: KeyCode,
+    pub import_mbf: KeyCode,
+    pub replay: KeyCode,
+    pub replay_pause: KeyCode,
+    pub replay_step: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            hint: KeyCode::H,
+            overlay: KeyCode::P,
+            undo: KeyCode::Z,
+            redo: KeyCode::Y,
+            save: KeyCode::S,
+            load: KeyCode::O,
+            export: KeyCode::E,
+            import_text: KeyCode::I,
+            import_mbf: KeyCode::B,
+            replay: KeyCode::R,
+            replay_pause: KeyCode::Space,
+            replay_step: KeyCode::N,
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Each action with its name in the file and whether it takes Ctrl.
+    fn actions(&mut self) -> [(&'static str, bool, &mut KeyCode); 12] {
+        [
+            ("hint", false, &mut self.hint),
+            ("overlay", false, &mut self.overlay),
+            ("undo", true, &mut self.undo),
+            ("redo", true, &mut self.redo),
+            ("save", true, &mut self.save),
+            ("load", true, &mut self.load),
+            ("export", true, &mut self.export),
+            ("import_text", true, &mut self.import_text),
+            ("import_mbf", true, &mut self.import_mbf),
+            ("replay", false, &mut self.replay),
+            ("replay_pause", false, &mut self.replay_pause),
+            ("replay_step", false, &mut self.replay_step),
+        ]
+    }
+}
+
+/// The keys a binding can name.
+const KEY_NAMES: [(&str, KeyCode); 42] = [
+    ("A", KeyCode::A),
+    ("B", KeyCode::B),
+    ("C", KeyCode::C),
+    ("D", KeyCode::D),
+    ("E", KeyCode::E),
+    ("F", KeyCode::F),
+    ("G", KeyCode::G),
+    ("H", KeyCode::H),
+    ("I", KeyCode::I),
+    ("J", KeyCode::J),
+    ("K", KeyCode::K),
+    ("L", KeyCode::L),
+    ("M", KeyCode::M),
+    ("N", KeyCode::N),
+    ("O", KeyCode::O),
+    ("P", KeyCode::P),
+    ("Q", KeyCode::Q),
+    ("R", KeyCode::R),
+    ("S", KeyCode::S),
+    ("T", KeyCode::T),
+    ("U", KeyCode::U),
+    ("V", KeyCode::V),
+    ("W", KeyCode::W),
+    ("X", KeyCode::X),
+    ("Y", KeyCode::Y),
+    ("Z", KeyCode::Z),
+    ("F1", KeyCode::F1),
+    ("F2", KeyCode::F2),
+    ("F3", KeyCode::F3),
+    ("F4", KeyCode::F4),
+    ("F5", KeyCode::F5),
+    ("F6", KeyCode::F6),
+    ("F7", KeyCode::F7),
+    ("F8", KeyCode::F8),
+    ("F9", KeyCode::F9),
+    ("F10", KeyCode::F10),
+    ("F11", KeyCode::F11),
+    ("F12", KeyCode::F12),
+    ("Space", KeyCode::Space),
+    ("Comma", KeyCode::Comma),
+    ("Period", KeyCode::Period),
+    ("Slash", KeyCode::Slash),
+];
+
+fn key_name(key: KeyCode) -> &'static str {
+    KEY_NAMES
+        .iter()
+        .find(|(_, code)| *code == key)
+        .map_or("?", |(name, _)| name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// The level the game opens with, unless the command line picks a board.
+    /// Only read at startup; the level buttons change the board while playing.
+    pub level: Level,
+    pub theme: Theme,
+    /// Screen pixels per world pixel.
+    pub camera_scale: f32,
+    /// World pixels per tile.
+    pub tile_size: f32,
+    pub first_click: FirstClick,
+    pub question_marks: bool,
+    pub sound: bool,
+    pub keys: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            level: Level::Base,
+            theme: Theme::Light,
+            camera_scale: CAMERA_SCALE,
+            tile_size: TILE_SIZE,
+            first_click: FirstClick::default(),
+            question_marks: false,
+            sound: true,
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+/// An entry of the file that was left at its default.
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    pub entry: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.entry, self.message)
+    }
+}
+
+fn named<T: Copy>(value: &ron::Value, names: &[(&str, T)]) -> Result<T, String> {
+    let expected = || {
+        let names = names.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        format!("expected one of \"{}\"", names.join("\", \""))
+    };
+    let ron::Value::String(text) = value else {
+        return Err(expected());
+    };
+    names
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(text))
+        .map(|(_, item)| *item)
+        .ok_or_else(expected)
+}
+
+fn number(value: &ron::Value, range: RangeInclusive<f32>) -> Result<f32, String> {
+    match value.clone().into_rust::<f32>() {
+        Ok(number) if range.contains(&number) => Ok(number),
+        _ => Err(format!(
+            "expected a number from {} to {}",
+            range.start(),
+            range.end()
+        )),
+    }
+}
+
+fn boolean(value: &ron::Value) -> Result<bool, String> {
+    match value {
+        ron::Value::Bool(value) => Ok(*value),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+/// The `(name: value, ...)` pairs of a RON struct.
+fn entries(value: &ron::Value) -> Option<Vec<(String, ron::Value)>> {
+    let ron::Value::Map(map) = value else {
+        return None;
+    };
+    map.iter()
+        .map(|(key, value)| match key {
+            ron::Value::String(key) => Some((key.clone(), value.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+impl Settings {
+    /// The board a new game starts with.
+    pub fn board_options(&self) -> BoardOptions {
+        let (width, height, bomb_count) = self.level.size();
+        BoardOptions {
+            first_click: self.first_click,
+            question_marks: self.question_marks,
+            ..BoardOptions::new(width, height, bomb_count)
+        }
+    }
+
+    /// Reads the settings in `text`, with every entry that had to be skipped.
+    /// Fails only when the text as a whole can't be read.
+    pub fn from_ron(text: &str) -> Result<(Self, Vec<Problem>), String> {
+        let value = ron::from_str::<ron::Value>(text).map_err(|error| error.to_string())?;
+        let entries = entries(&value).ok_or("expected settings in the form (name: value, ...)")?;
+
+        let mut settings = Settings::default();
+        let mut problems = Vec::new();
+        let levels = Level::ALL.map(|level| (level.name(), level));
+        for (entry, value) in entries {
+            let applied = match entry.as_str() {
+                "level" => named(&value, &levels).map(|level| settings.level = level),
+                "theme" => named(&value, &[("Light", Theme::Light), ("Dark", Theme::Dark)])
+                    .map(|theme| settings.theme = theme),
+                "camera_scale" => {
+                    number(&value, CAMERA_SCALE_RANGE).map(|scale| settings.camera_scale = scale)
+                }
+                "tile_size" => {
+                    number(&value, TILE_SIZE_RANGE).map(|size| settings.tile_size = size)
+                }
+                "first_click" => named(
+                    &value,
+                    &[
+                        ("Opening", FirstClick::Opening),
+                        ("Safe", FirstClick::Safe),
+                        ("Any", FirstClick::Unprotected),
+                    ],
+                )
+                .map(|first_click| settings.first_click = first_click),
+                "question_marks" => boolean(&value).map(|on| settings.question_marks = on),
+                "sound" => boolean(&value).map(|on| settings.sound = on),
+                "keys" => settings.read_keys(&value, &mut problems),
+                _ => Err("not a setting".to_string()),
+            };
+            if let Err(message) = applied {
+                problems.push(Problem { entry, message });
+            }
+        }
+        Ok((settings, problems))
+    }
+
+    fn read_keys(&mut self, value: &ron::Value, problems: &mut Vec<Problem>) -> Result<(), String> {
+        let entries = entries(value).ok_or("expected keys in the form (action: \"key\", ...)")?;
+        let mut read = Vec::new();
+        for (action, value) in entries {
+            let entry = format!("keys.{}", action);
+            let mut actions = self.keys.actions();
+            let Some((name, _, key)) = actions.iter_mut().find(|(name, ..)| *name == action) else {
+                problems.push(Problem {
+                    entry,
+                    message: "not an action".to_string(),
+                });
+                continue;
+            };
+            let digit = matches!(&value, ron::Value::String(text)
+                if text.len() == 1 && text.chars().all(|c| c.is_ascii_digit()));
+            match named(&value, &KEY_NAMES) {
+                Ok(code) => {
+                    **key = code;
+                    read.push(*name);
+                }
+                Err(_) if digit => problems.push(Problem {
+                    entry,
+                    message: "digits are typed into the seed and custom board dialogs".to_string(),
+                }),
+                Err(_) => problems.push(Problem {
+                    entry,
+                    message: "expected a letter, F1 to F12, Space, Comma, Period or Slash"
+                        .to_string(),
+                }),
+            }
+        }
+
+        // A key that two actions share is taken back to its default, until none is shared.
+        let mut defaults = KeyBindings::default();
+        loop {
+            let actions = self.keys.actions();
+            let clash = (0..actions.len()).find_map(|a| {
+                (0..actions.len())
+                    .find(|b| {
+                        a != *b && *actions[a].2 == *actions[*b].2 && read.contains(&actions[a].0)
+                    })
+                    .map(|b| (a, b))
+            });
+            let Some((a, b)) = clash else {
+                break;
+            };
+            let (action, other) = (actions[a].0, actions[b].0);
+            problems.push(Problem {
+                entry: format!("keys.{}", action),
+                message: format!("{} is already bound to {}", key_name(*actions[a].2), other),
+            });
+            *actions[a].2 = *defaults.actions()[a].2;
+            read.retain(|name| *name != action);
+        }
+        Ok(())
+    }
+
+    /// The settings as a file to edit, with every entry written out.
+    pub fn to_ron(&self) -> String {
+        let first_click = match self.first_click {
+            FirstClick::Opening => "Opening",
+            FirstClick::Safe => "Safe",
+            FirstClick::Unprotected => "Any",
+        };
+        let theme = match self.theme {
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+        };
+        let mut keys = self.keys;
+        let keys = keys
+            .actions()
+            .into_iter()
+            .map(|(action, ctrl, key)| {
+                let note = if ctrl { " // with Ctrl" } else { "" };
+                format!("        {}: \"{}\",{}\n", action, key_name(*key), note)
+            })
+            .collect::<String>();
+
+        format!(
+            "// Minesweeper settings. Changes are picked up while the game runs.
+(
+    // Base, Pro or Expert, unless the command line picks a board. Read at startup only.
+    level: \"{}\",
+    // Light or Dark.
+    theme: \"{}\",
+    // Screen pixels per world pixel, {} to {}.
+    camera_scale: {:?},
+    // World pixels per tile, {} to {}.
+    tile_size: {:?},
+    // Opening, Safe or Any, for new games.
+    first_click: \"{}\",
+    question_marks: {},
+    sound: {},
+    // A letter, F1 to F12, Space, Comma, Period or Slash.
+    keys: (
+{}    ),
+)
+",
+            self.level.name(),
+            theme,
+            CAMERA_SCALE_RANGE.start(),
+            CAMERA_SCALE_RANGE.end(),
+            self.camera_scale,
+            TILE_SIZE_RANGE.start(),
+            TILE_SIZE_RANGE.end(),
+            self.tile_size,
+            first_click,
+            self.question_marks,
+            self.sound,
+            keys,
+        )
+    }
+}
+
+/// `settings.ron` under `$XDG_CONFIG_HOME` or `~/.config`, `%APPDATA%` on
+/// Windows and `~/Library/Application Support` on macOS.
+pub fn settings_path() -> Option<PathBuf> {
+    let home = || env::var_os("HOME").map(PathBuf::from);
+    let config_dir = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".config")))
+    };
+    config_dir.map(|dir| dir.join(CONFIG_DIR).join(SETTINGS_FILE))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// The settings to start with. A missing file is written out with the
+/// defaults so there is something to edit. Problems are kept in the watch
+/// and logged once the game is running.
+pub fn load_settings() -> (Settings, SettingsWatch) {
+    let mut watch = SettingsWatch::new(settings_path());
+    let Some(path) = watch.path.clone() else {
+        watch
+            .warnings
+            .push("no config directory found, using the default settings".to_string());
+        return (Settings::default(), watch);
+    };
+
+    let settings = match fs::read_to_string(&path) {
+        Ok(text) => match Settings::from_ron(&text) {
+            Ok((settings, problems)) => {
+                watch.warnings.extend(
+                    problems
+                        .iter()
+                        .map(|problem| format!("{}: {}", path.display(), problem)),
+                );
+                settings
+            }
+            Err(error) => {
+                watch.warnings.push(format!(
+                    "{}: {}, using the default settings",
+                    path.display(),
+                    error
+                ));
+                Settings::default()
+            }
+        },
+        Err(_) if !path.exists() => {
+            let settings = Settings::default();
+            let written = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(&path, settings.to_ron()));
+            if let Err(error) = written {
+                watch
+                    .warnings
+                    .push(format!("could not write {}: {}", path.display(), error));
+            }
+            settings
+        }
+        Err(error) => {
+            watch
+                .warnings
+                .push(format!("could not read {}: {}", path.display(), error));
+            Settings::default()
+        }
+    };
+    watch.modified = modified(&path);
+    (settings, watch)
+}
+
+/// Polls the file and swaps in the new settings when it changes. A file that
+/// can't be read at all keeps the settings as they are.
+pub fn settings_watch_system(
+    time: Res<Time>,
+    mut watch: ResMut<SettingsWatch>,
+    mut settings: ResMut<Settings>,
+    mut board_options: ResMut<BoardOptions>,
+    mut message: ResMut<Message>,
+) {
+    for warning in watch.warnings.drain(..) {
+        warn!("{}", warning);
+    }
+    if !watch.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Some(path) = watch.path.clone() else {
+        return;
+    };
+    let modified = modified(&path);
+    if modified == watch.modified {
+        return;
+    }
+    watch.modified = modified;
+
+    let loaded = fs::read_to_string(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|text| Settings::from_ron(&text));
+    let (new_settings, problems) = match loaded {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            warn!("{}: {}", path.display(), error);
+            message.0 = format!("Settings not reloaded: {}", error);
+            return;
+        }
+    };
+    for problem in problems.iter() {
+        warn!("{}: {}", path.display(), problem);
+    }
+    message.0 = match problems.len() {
+        0 => "Settings reloaded".to_string(),
+        count => format!("Settings reloaded, {} entries skipped", count),
+    };
+
+    // Like the option buttons, these apply from the next game. The level is only
+    // read at startup, so a reload doesn't throw away the board being played.
+    if new_settings.first_click != settings.first_click {
+        board_options.first_click = new_settings.first_click;
+    }
+    if new_settings.question_marks != settings.question_marks {
+        board_options.question_marks = new_settings.question_marks;
+    }
+    if *settings != new_settings {
+        *settings = new_settings;
+    }
+}
+
+pub fn theme_system(
+    settings: Res<Settings>,
+    mut clear_color: ResMut<ClearColor>,
+    mut text_query: Query<&mut Text, With<ThemedText>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    clear_color.0 = settings.theme.background();
+    for mut text in text_query.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = settings.theme.text();
+        }
+    }
+}
+
+pub fn camera_scale_system(
+    settings: Res<Settings>,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut transform in camera_query.iter_mut() {
+        transform.scale = Vec3::splat(1.0 / settings.camera_scale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_defaults_read_back() {
+        let settings = Settings {
+            level: Level::Expert,
+            theme: Theme::Dark,
+            camera_scale: 3.0,
+            sound: false,
+            keys: KeyBindings {
+                hint: KeyCode::F1,
+                ..default()
+            },
+            ..default()
+        };
+        assert_eq!(
+            Settings::from_ron(&settings.to_ron()),
+            Ok((settings, vec![]))
+        );
+        assert_eq!(
+            Settings::from_ron(&Settings::default().to_ron()),
+            Ok((Settings::default(), vec![]))
+        );
+    }
+
+    #[test]
+    fn written_key_list_matches_the_key_names() {
+        let text = Settings::default().to_ron();
+        let note = text
+            .lines()
+            .find(|line| line.contains("F1 to F12"))
+            .unwrap();
+        assert_eq!(
+            note.trim(),
+            "// A letter, F1 to F12, Space, Comma, Period or Slash."
+        );
+
+        let listed = ('A'..='Z')
+            .map(String::from)
+            .chain((1..=12).map(|n| format!("F{}", n)))
+            .chain(["Space", "Comma", "Period", "Slash"].map(String::from))
+            .collect::<Vec<_>>();
+        let names = KEY_NAMES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(listed, names);
+    }
+
+    #[test]
+    fn bad_entries_keep_their_defaults() {
+        let (settings, problems) = Settings::from_ron(
+            r#"(
+                level: "Pro",
+                theme: "Sepia",
+                tile_size: 1000,
+                first_click: "safe",
+                sound: "loud",
+                colour: "red",
+                keys: (hint: "F5", undo: "Ctrl", jump: "J"),
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            settings,
+            Settings {
+                level: Level::Pro,
+                first_click: FirstClick::Safe,
+                keys: KeyBindings {
+                    hint: KeyCode::F5,
+                    ..default()
+                },
+                ..default()
+            }
+        );
+        let mut entries = problems
+            .iter()
+            .map(|problem| problem.entry.as_str())
+            .collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(
+            entries,
+            [
+                "colour",
+                "keys.jump",
+                "keys.undo",
+                "sound",
+                "theme",
+                "tile_size"
+            ]
+        );
+
+        assert!(Settings::from_ron("(level: ").is_err());
+        assert!(Settings::from_ron("5").is_err());
+    }
+
+    #[test]
+    fn digits_and_shared_keys_are_rejected() {
+        let (settings, problems) = Settings::from_ron(
+            r#"(keys: (hint: "3", overlay: "R", undo: "U", redo: "Z", replay: "X"))"#,
+        )
+        .unwrap();
+
+        // Clashes are looked for once every key is read, so keys can move around.
+        assert_eq!(
+            settings.keys,
+            KeyBindings {
+                overlay: KeyCode::R,
+                undo: KeyCode::U,
+                redo: KeyCode::Z,
+                replay: KeyCode::X,
+                ..default()
+            }
+        );
+        assert_eq!(
+            problems,
+            [Problem {
+                entry: "keys.hint".to_string(),
+                message: "digits are typed into the seed and custom board dialogs".to_string(),
+            }]
+        );
+
+        let (settings, problems) = Settings::from_ron(r#"(keys: (hint: "P", save: "N"))"#).unwrap();
+        assert_eq!(settings.keys, KeyBindings::default());
+        assert_eq!(
+            problems,
+            [
+                Problem {
+                    entry: "keys.hint".to_string(),
+                    message: "P is already bound to overlay".to_string(),
+                },
+                Problem {
+                    entry: "keys.save".to_string(),
+                    message: "N is already bound to replay_step".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,556 @@
+use crate::engine::{Board, GameStatus, Tile};
+
+/// Why a tile is known to be safe or a mine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// The number already has all of its mines marked.
+    Satisfied((u32, u32)),
+    /// The number has exactly as many hidden neighbours as missing mines.
+    Full((u32, u32)),
+    /// Every hidden neighbour of the first number also touches the second one.
+    Subset((u32, u32), (u32, u32)),
+    /// The mines left on the counter settle every hidden tile.
+    MineCount,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deduction {
+    pub coord: (u32, u32),
+    pub mine: bool,
+    pub reason: Reason,
+}
+
+impl Deduction {
+    /// A short sentence saying why the move is certain.
+    pub fn explain(&self, board: &Board) -> String {
+        let number =
+            |pos: (u32, u32)| format!("the {} at ({},{})", board.bomb_count_at(pos), pos.0, pos.1);
+        match self.reason {
+            Reason::Satisfied(origin) => format!("{} is already satisfied", number(origin)),
+            Reason::Full(origin) => format!("{} needs every hidden neighbour", number(origin)),
+            Reason::Subset(a, b) => format!("{} settles the rest of {}", number(a), number(b)),
+            Reason::MineCount if self.mine => "every hidden tile is a mine".to_string(),
+            Reason::MineCount => "all mines are already marked".to_string(),
+        }
+    }
+}
+
+/// An opened number: how many mines are still missing among its hidden neighbours.
+struct Constraint {
+    origin: (u32, u32),
+    cells: Vec<(u32, u32)>,
+    mines: usize,
+}
+
+fn constraints(board: &Board) -> Vec<Constraint> {
+    board
+        .coords()
+        .filter(|coord| board.tile(*coord) == Some(Tile::Opened) && !board.is_bomb_at(*coord))
+        .filter_map(|origin| {
+            let around = board.safe_square_at(origin);
+            let marked = around
+                .iter()
+                .filter(|pos| board.tile(**pos) == Some(Tile::Marked))
+                .count();
+            let cells = around
+                .into_iter()
+                .filter(|pos| board.tile(*pos).is_some_and(|tile| tile.is_hidden()))
+                .collect::<Vec<_>>();
+            let mines = usize::try_from(board.bomb_count_at(origin))
+                .ok()?
+                .checked_sub(marked)?;
+
+            if cells.is_empty() || mines > cells.len() {
+                return None;
+            }
+            Some(Constraint {
+                origin,
+                cells,
+                mines,
+            })
+        })
+        .collect()
+}
+
+fn push(found: &mut Vec<Deduction>, cells: &[(u32, u32)], mine: bool, reason: Reason) {
+    for coord in cells {
+        if !found.iter().any(|d| d.coord == *coord) {
+            found.push(Deduction {
+                coord: *coord,
+                mine,
+                reason,
+            });
+        }
+    }
+}
+
+/// Every move that follows from what the player can see, using the simplest rule that finds any.
+/// Marks are taken as mines.
+pub fn deduce(board: &Board) -> Vec<Deduction> {
+    let constraints = constraints(board);
+    let mut found: Vec<Deduction> = vec![];
+    for c in constraints.iter() {
+        if c.mines == 0 {
+            push(&mut found, &c.cells, false, Reason::Satisfied(c.origin));
+        } else if c.mines == c.cells.len() {
+            push(&mut found, &c.cells, true, Reason::Full(c.origin));
+        }
+    }
+    if !found.is_empty() {
+        return found;
+    }
+
+    for a in constraints.iter() {
+        for b in constraints.iter() {
+            if a.cells.len() >= b.cells.len() || !a.cells.iter().all(|pos| b.cells.contains(pos)) {
+                continue;
+            }
+            let rest = b
+                .cells
+                .iter()
+                .copied()
+                .filter(|pos| !a.cells.contains(pos))
+                .collect::<Vec<_>>();
+            let reason = Reason::Subset(a.origin, b.origin);
+
+            if a.mines == b.mines {
+                push(&mut found, &rest, false, reason);
+            } else if b.mines.checked_sub(a.mines) == Some(rest.len()) {
+                push(&mut found, &rest, true, reason);
+            }
+        }
+    }
+    if !found.is_empty() {
+        return found;
+    }
+
+    let hidden = board
+        .coords()
+        .filter(|coord| board.tile(*coord).is_some_and(|tile| tile.is_hidden()))
+        .collect::<Vec<_>>();
+    let left = board.options.bomb_count as usize;
+    match left.checked_sub(board.marked_count() as usize) {
+        Some(0) => push(&mut found, &hidden, false, Reason::MineCount),
+        Some(left) if left == hidden.len() => push(&mut found, &hidden, true, Reason::MineCount),
+        _ => (),
+    }
+    found
+}
+
+/// Plays the board from `first` using only deductions and tells whether it gets to a win.
+pub fn is_solvable(board: &Board, first: (u32, u32)) -> bool {
+    let mut board = board.clone();
+    board.uncover(first);
+
+    while board.status == GameStatus::Playing {
+        let found = deduce(&board);
+        if found.is_empty() {
+            return false;
+        }
+        for deduction in found {
+            if deduction.mine {
+                board.mark(deduction.coord);
+            } else {
+                board.uncover(deduction.coord);
+            }
+        }
+    }
+    board.status == GameStatus::Won
+}
+
+/// The hidden tile least likely to be a mine. Uses the exact chances when they
+/// can be counted, otherwise a rough chance taken from the worst number around
+/// each tile, or the mine density away from the numbers.
+pub fn safest_guess(board: &Board) -> Option<((u32, u32), f32)> {
+    if let Some(chances) = probabilities(board) {
+        return chances
+            .into_iter()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(coord, chance)| (coord, chance as f32));
+    }
+
+    let constraints = constraints(board);
+    let hidden = board
+        .coords()
+        .filter(|coord| board.tile(*coord).is_some_and(|tile| tile.is_hidden()))
+        .collect::<Vec<_>>();
+    let left = board
+        .options
+        .bomb_count
+        .saturating_sub(board.marked_count()) as f32;
+    let density = left / hidden.len().max(1) as f32;
+
+    hidden
+        .into_iter()
+        .map(|coord| {
+            let risk = constraints
+                .iter()
+                .filter(|c| c.cells.contains(&coord))
+                .map(|c| c.mines as f32 / c.cells.len() as f32)
+                .reduce(f32::max)
+                .unwrap_or(density);
+            (coord, risk)
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Search steps allowed per frontier group before the exact count gives up.
+const ENUMERATION_LIMIT: usize = 2_000_000;
+
+/// Mine layouts of one group of frontier tiles that touch the same numbers.
+struct Group {
+    cells: Vec<(u32, u32)>,
+    /// Solutions by number of mines in the group.
+    solutions: Vec<f64>,
+    /// How often each cell is a mine, by number of mines in the group.
+    mine_counts: Vec<Vec<f64>>,
+}
+
+struct Search<'a> {
+    links: &'a [Vec<usize>],
+    targets: &'a [usize],
+    placed: Vec<usize>,
+    open: Vec<usize>,
+    mines: Vec<bool>,
+    steps: usize,
+}
+
+impl Search<'_> {
+    fn run(&mut self, index: usize, group: &mut Group) -> bool {
+        self.steps += 1;
+        if self.steps > ENUMERATION_LIMIT {
+            return false;
+        }
+        if index == self.mines.len() {
+            let k = self.mines.iter().filter(|mine| **mine).count();
+            group.solutions[k] += 1.0;
+            for (cell, mine) in self.mines.iter().enumerate() {
+                if *mine {
+                    group.mine_counts[k][cell] += 1.0;
+                }
+            }
+            return true;
+        }
+
+        for mine in [false, true] {
+            let fits = self.links[index].iter().all(|c| {
+                let placed = self.placed[*c] + mine as usize;
+                let open = self.open[*c] - 1;
+                placed <= self.targets[*c] && placed + open >= self.targets[*c]
+            });
+            if !fits {
+                continue;
+            }
+            for c in self.links[index].iter() {
+                self.placed[*c] += mine as usize;
+                self.open[*c] -= 1;
+            }
+            self.mines[index] = mine;
+            let finished = self.run(index + 1, group);
+            for c in self.links[index].iter() {
+                self.placed[*c] -= mine as usize;
+                self.open[*c] += 1;
+            }
+            if !finished {
+                return false;
+            }
+        }
+        self.mines[index] = false;
+        true
+    }
+}
+
+fn groups(constraints: &[Constraint]) -> Option<Vec<Group>> {
+    let mut owner = (0..constraints.len()).collect::<Vec<_>>();
+    fn root(owner: &mut [usize], i: usize) -> usize {
+        if owner[i] != i {
+            owner[i] = root(owner, owner[i]);
+        }
+        owner[i]
+    }
+    for a in 0..constraints.len() {
+        for b in (a + 1)..constraints.len() {
+            if constraints[a]
+                .cells
+                .iter()
+                .any(|pos| constraints[b].cells.contains(pos))
+            {
+                let (ra, rb) = (root(&mut owner, a), root(&mut owner, b));
+                owner[ra] = rb;
+            }
+        }
+    }
+
+    let mut groups = vec![];
+    for r in 0..constraints.len() {
+        if root(&mut owner, r) != r {
+            continue;
+        }
+        let members = (0..constraints.len())
+            .filter(|i| root(&mut owner, *i) == r)
+            .collect::<Vec<_>>();
+
+        // Walk the cells number by number so each number is filled in as early as possible.
+        let mut cells: Vec<(u32, u32)> = vec![];
+        for i in members.iter() {
+            for pos in constraints[*i].cells.iter() {
+                if !cells.contains(pos) {
+                    cells.push(*pos);
+                }
+            }
+        }
+        let links = cells
+            .iter()
+            .map(|pos| {
+                (0..members.len())
+                    .filter(|m| constraints[members[*m]].cells.contains(pos))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let targets = members
+            .iter()
+            .map(|i| constraints[*i].mines)
+            .collect::<Vec<_>>();
+
+        let mut group = Group {
+            solutions: vec![0.0; cells.len() + 1],
+            mine_counts: vec![vec![0.0; cells.len()]; cells.len() + 1],
+            cells,
+        };
+        let mut search = Search {
+            links: &links,
+            targets: &targets,
+            placed: vec![0; members.len()],
+            open: members
+                .iter()
+                .map(|i| constraints[*i].cells.len())
+                .collect(),
+            mines: vec![false; group.cells.len()],
+            steps: 0,
+        };
+        if !search.run(0, &mut group) {
+            return None;
+        }
+        groups.push(group);
+    }
+    Some(groups)
+}
+
+fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut out = vec![0.0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            out[i + j] += x * y;
+        }
+    }
+    out
+}
+
+/// The exact chance of a mine under every hidden tile, given the numbers,
+/// the marks (taken as mines) and the total mine count. `None` when the marks
+/// contradict the numbers or the board is too open to count in time.
+pub fn probabilities(board: &Board) -> Option<Vec<((u32, u32), f64)>> {
+    if !board.options.plain_numbers() {
+        return None;
+    }
+    let constraints = constraints(board);
+    let groups = groups(&constraints)?;
+    let hidden = board
+        .coords()
+        .filter(|coord| board.tile(*coord).is_some_and(|tile| tile.is_hidden()))
+        .collect::<Vec<_>>();
+    let rest = hidden
+        .iter()
+        .copied()
+        .filter(|pos| !groups.iter().any(|g| g.cells.contains(pos)))
+        .collect::<Vec<_>>();
+    let left = (board.options.bomb_count as usize).checked_sub(board.marked_count() as usize)?;
+
+    // ln C(rest, j), scaled later so huge boards stay in range.
+    let mut ln_choose = vec![0.0f64; left + 1];
+    for j in 1..=left.min(rest.len()) {
+        ln_choose[j] = ln_choose[j - 1] + ((rest.len() - j + 1) as f64).ln() - (j as f64).ln();
+    }
+    let ln_max = ln_choose[..=left.min(rest.len())]
+        .iter()
+        .copied()
+        .fold(f64::MIN, f64::max);
+    let weight = |k: usize| -> f64 {
+        match left.checked_sub(k) {
+            Some(j) if j <= rest.len() => (ln_choose[j] - ln_max).exp(),
+            _ => 0.0,
+        }
+    };
+
+    let all = groups
+        .iter()
+        .fold(vec![1.0], |acc, g| convolve(&acc, &g.solutions));
+    let total = all
+        .iter()
+        .enumerate()
+        .map(|(k, n)| n * weight(k))
+        .sum::<f64>();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let mut result = vec![];
+    for (i, group) in groups.iter().enumerate() {
+        let others = groups
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .fold(vec![1.0], |acc, (_, g)| convolve(&acc, &g.solutions));
+        let mut chance = vec![0.0; group.cells.len()];
+        for (k, counts) in group.mine_counts.iter().enumerate() {
+            let w = others
+                .iter()
+                .enumerate()
+                .map(|(o, n)| n * weight(k + o))
+                .sum::<f64>();
+            for (cell, count) in counts.iter().enumerate() {
+                chance[cell] += count * w;
+            }
+        }
+        for (cell, pos) in group.cells.iter().enumerate() {
+            result.push((*pos, chance[cell] / total));
+        }
+    }
+
+    if !rest.is_empty() {
+        let expected = all
+            .iter()
+            .enumerate()
+            .map(|(k, n)| n * weight(k) * left.saturating_sub(k) as f64)
+            .sum::<f64>()
+            / total;
+        let chance = expected / rest.len() as f64;
+        result.extend(rest.into_iter().map(|pos| (pos, chance)));
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{engine::BoardOptions, format::parse_text};
+
+    #[test]
+    fn solvable_needs_no_guess() {
+        let board = parse_text("*..\n...\n...\n").unwrap();
+        assert!(is_solvable(&board, (2, 0)));
+
+        // One mine among three cells that all touch the only number.
+        let board = parse_text("*.\n..\n").unwrap();
+        assert!(!is_solvable(&board, (1, 0)));
+    }
+
+    /// Chances by trying every way to place the mines left on the hidden tiles.
+    fn brute_force(board: &Board) -> Vec<((u32, u32), f64)> {
+        let hidden = board
+            .coords()
+            .filter(|coord| board.tile(*coord).is_some_and(|tile| tile.is_hidden()))
+            .collect::<Vec<_>>();
+        let left = board.options.bomb_count - board.marked_count();
+        let numbers = board
+            .coords()
+            .filter(|coord| board.tile(*coord) == Some(Tile::Opened))
+            .collect::<Vec<_>>();
+
+        let mut layouts = 0.0;
+        let mut mines = vec![0.0; hidden.len()];
+        for mask in 0u32..1 << hidden.len() {
+            if mask.count_ones() != left {
+                continue;
+            }
+            let mine_at = |pos: &(u32, u32)| {
+                board.tile(*pos) == Some(Tile::Marked)
+                    || hidden
+                        .iter()
+                        .position(|h| h == pos)
+                        .is_some_and(|i| mask & 1 << i != 0)
+            };
+            let fits = numbers.iter().all(|number| {
+                let around = board.safe_square_at(*number);
+                around.iter().filter(|pos| mine_at(pos)).count() as i32
+                    == board.bomb_count_at(*number)
+            });
+            if fits {
+                layouts += 1.0;
+                for (i, count) in mines.iter_mut().enumerate() {
+                    *count += (mask >> i & 1) as f64;
+                }
+            }
+        }
+        hidden
+            .into_iter()
+            .zip(mines)
+            .map(|(pos, count)| (pos, count / layouts))
+            .collect()
+    }
+
+    #[test]
+    fn chances_match_counting_every_layout() {
+        let mut checked = 0;
+        for seed in 0..40 {
+            let mut board = Board::default();
+            board.reset(&BoardOptions::new(5, 4, 5).with_seed(Some(seed)));
+            board.uncover((2, 2));
+            // Marking a mine next to a number makes the marks part of the count.
+            if let Some(mine) = board.coords().find(|pos| {
+                board.is_bomb_at(*pos)
+                    && board
+                        .safe_square_at(*pos)
+                        .iter()
+                        .any(|n| board.tile(*n) == Some(Tile::Opened))
+            }) {
+                if seed % 2 == 0 {
+                    board.mark(mine);
+                }
+            }
+            if board.status != GameStatus::Playing {
+                continue;
+            }
+
+            let mut expected = brute_force(&board);
+            let mut chances = probabilities(&board).unwrap();
+            expected.sort_by_key(|(pos, _)| *pos);
+            chances.sort_by_key(|(pos, _)| *pos);
+            assert_eq!(chances.len(), expected.len());
+            for ((pos, chance), (_, exact)) in chances.iter().zip(expected.iter()) {
+                assert!((chance - exact).abs() < 1e-9, "seed {} at {:?}", seed, pos);
+            }
+
+            let lowest = expected
+                .iter()
+                .map(|(_, chance)| *chance)
+                .fold(1.0, f64::min);
+            let (guess, chance) = safest_guess(&board).unwrap();
+            assert!((chance as f64 - lowest).abs() < 1e-6);
+            assert!(expected
+                .iter()
+                .any(|(pos, exact)| *pos == guess && (exact - lowest).abs() < 1e-9));
+            checked += 1;
+        }
+        assert!(checked > 10);
+    }
+
+    #[test]
+    fn too_many_layouts_fall_back_to_a_rough_guess() {
+        // Every other bottom tile is opened and sees two mines among five hidden tiles,
+        // so one long chain of numbers has far more layouts than the search allows.
+        let top = (0..61).map(|x| if x % 2 == 1 { '*' } else { '.' });
+        let bottom = (0..61).map(|x| if x % 2 == 0 { '+' } else { '.' });
+        let text = format!(
+            "{}\n{}\n",
+            top.collect::<String>(),
+            bottom.collect::<String>()
+        );
+        let board = parse_text(&text).unwrap();
+
+        assert_eq!(probabilities(&board), None);
+        let (guess, chance) = safest_guess(&board).unwrap();
+        assert!(board.tile(guess).is_some_and(|tile| tile.is_hidden()));
+        assert!((0.0..=1.0).contains(&chance));
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use bevy::{
+    audio::{Pitch, PitchBundle},
+    prelude::*,
+};
+
+use crate::{
+    resources::Settings,
+    tile::{TileMarkEvent, TileUncoverEvent},
+    GameLoseEvent, GameWinEvent,
+};
+
+/// Frequency in hertz and length in milliseconds of each beep.
+const UNCOVER_TONE: (f32, u64) = (880.0, 25);
+const MARK_TONE: (f32, u64) = (660.0, 25);
+const LOSE_TONE: (f32, u64) = (110.0, 400);
+const WIN_TONE: (f32, u64) = (1320.0, 300);
+
+/// Short generated beeps, so there are no sound files to ship.
+pub fn sound_system(
+    mut commands: Commands,
+    mut pitch_assets: ResMut<Assets<Pitch>>,
+    settings: Res<Settings>,
+    mut tile_uncover_events: EventReader<TileUncoverEvent>,
+    mut tile_mark_events: EventReader<TileMarkEvent>,
+    mut game_lose_events: EventReader<GameLoseEvent>,
+    mut game_win_events: EventReader<GameWinEvent>,
+) {
+    // Every reader is drained, so nothing plays late once sound is turned on.
+    let tones = [
+        (tile_uncover_events.read().count() > 0, UNCOVER_TONE),
+        (tile_mark_events.read().count() > 0, MARK_TONE),
+        (game_lose_events.read().count() > 0, LOSE_TONE),
+        (game_win_events.read().count() > 0, WIN_TONE),
+    ];
+    if !settings.sound {
+        return;
+    }
+
+    for (_, (frequency, millis)) in tones.into_iter().filter(|(played, _)| *played) {
+        commands.spawn(PitchBundle {
+            source: pitch_assets.add(Pitch::new(frequency, Duration::from_millis(millis))),
+            settings: PlaybackSettings::DESPAWN,
+        });
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::Level,
+    engine::{Board, BoardOptions, FirstClick, GameStatus, Neighbourhood, Shape},
+    save::{read_ron, write_ron, SaveError},
+};
+
+pub const STATS_PATH: &str = "minesweeper-stats.ron";
+
+/// Results for one kind of board.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Record {
+    pub played: u32,
+    pub wins: u32,
+    pub current_streak: u32,
+    pub best_streak: u32,
+    /// Seconds, over won games only.
+    pub best_time: Option<f32>,
+    pub total_time: f32,
+    /// Games where moves were undone; they are counted here and nowhere else.
+    pub assisted: u32,
+}
+
+impl Record {
+    pub fn win_rate(&self) -> f32 {
+        match self.played {
+            0 => 0.0,
+            played => self.wins as f32 / played as f32,
+        }
+    }
+
+    pub fn average_time(&self) -> Option<f32> {
+        (self.wins > 0).then(|| self.total_time / self.wins as f32)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    /// Keyed by `category`.
+    pub records: BTreeMap<String, Record>,
+}
+
+/// The level name for a standard board, otherwise its size, followed by every
+/// option that changes how hard the board is.
+pub fn category(options: &BoardOptions) -> String {
+    let size = (options.width, options.height, options.bomb_count);
+    let mut parts = vec![Level::ALL
+        .iter()
+        .find(|level| level.size() == size)
+        .map_or_else(
+            || format!("{}x{}/{}", size.0, size.1, size.2),
+            |level| level.name().to_string(),
+        )];
+
+    match options.shape {
+        Shape::Square => (),
+        Shape::Hex => parts.push("hex".to_string()),
+        Shape::Triangle => parts.push("triangle".to_string()),
+    }
+    if options.wraps() {
+        parts.push("wrap".to_string());
+    }
+    if options.shape == Shape::Square {
+        match options.neighbourhood {
+            Neighbourhood::Moore => (),
+            Neighbourhood::Knight => parts.push("knight".to_string()),
+            Neighbourhood::Cross => parts.push("cross".to_string()),
+            Neighbourhood::Ring => parts.push("ring".to_string()),
+        }
+    }
+    if options.multi_mine {
+        parts.push("multi-mine".to_string());
+    }
+    if options.anti_mine_count > 0 {
+        parts.push(format!("{} anti-mines", options.anti_mine_count));
+    }
+    match options.first_click {
+        FirstClick::Opening => (),
+        FirstClick::Safe => parts.push("safe start".to_string()),
+        FirstClick::Unprotected => parts.push("unprotected".to_string()),
+    }
+    if options.no_guess {
+        parts.push("no guess".to_string());
+    }
+    parts.join(", ")
+}
+
+impl Stats {
+    /// The saved stats, or empty ones before the first game is finished.
+    pub fn load() -> Result<Self, SaveError> {
+        read_ron(STATS_PATH)
+    }
+
+    pub fn save(&self) -> Result<(), SaveError> {
+        write_ron(STATS_PATH, self)
+    }
+
+    /// Counts a finished game, `time` being its length in seconds.
+    /// A game still being played isn't counted, nor one loaded already finished.
+    pub fn record_game(&mut self, board: &Board, time: f32) {
+        if board.status == GameStatus::Playing || board.restored {
+            return;
+        }
+        let record = self.records.entry(category(&board.options)).or_default();
+        if board.assisted {
+            record.assisted += 1;
+            return;
+        }
+
+        record.played += 1;
+        match board.status {
+            GameStatus::Won => {
+                record.wins += 1;
+                record.current_streak += 1;
+                record.best_streak = record.best_streak.max(record.current_streak);
+                record.best_time = Some(record.best_time.map_or(time, |best| best.min(time)));
+                record.total_time += time;
+            }
+            GameStatus::Lost => record.current_streak = 0,
+            GameStatus::Playing => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undone_loss_is_counted_once_as_assisted() {
+        let mut board = Board::default();
+        board.reset(&BoardOptions {
+            first_click: FirstClick::Unprotected,
+            ..BoardOptions::new(2, 1, 1).with_seed(Some(0))
+        });
+        let mine = board
+            .coords()
+            .find(|coord| board.is_bomb_at(*coord))
+            .unwrap();
+        let safe = board
+            .coords()
+            .find(|coord| !board.is_bomb_at(*coord))
+            .unwrap();
+        let mut stats = Stats::default();
+
+        let before = board.clone();
+        board.uncover(mine);
+        assert_eq!(board.status, GameStatus::Lost);
+        // Nothing is final while the move can still be undone.
+        board = before;
+        board.assisted = true;
+        stats.record_game(&board, 1.0);
+        assert_eq!(stats, Stats::default());
+
+        board.uncover(safe);
+        assert_eq!(board.status, GameStatus::Won);
+        stats.record_game(&board, 2.0);
+        let record = &stats.records[&category(&board.options)];
+        assert_eq!(
+            *record,
+            Record {
+                assisted: 1,
+                ..Record::default()
+            }
+        );
+
+        board.reset(&BoardOptions::new(2, 1, 1).with_seed(Some(0)));
+        let safe = board
+            .coords()
+            .find(|coord| !board.is_bomb_at(*coord))
+            .unwrap();
+        board.uncover(safe);
+        stats.record_game(&board, 3.0);
+        let record = &stats.records[&category(&board.options)];
+        assert_eq!(
+            (record.played, record.wins, record.best_time),
+            (1, 1, Some(3.0))
+        );
+    }
+}
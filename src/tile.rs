@@ -1,28 +1,27 @@
+use std::collections::HashMap;
+
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
 
 use crate::{
+    components::CountLabel,
+    engine::{points_up, GameStatus, Shape, Tile},
     resources::{
-        Board, BoardOptions, LastStep, BOMB_INDEX, MARKED_INDEX, OPENED_INDEX, UNOPENED_INDEX,
+        Board, Hint, LastStep, Message, ProbabilityOverlay, ANTI_MARKED_INDEX, ANTI_MINE_INDEX,
+        ANTI_MINE_RED_INDEX, BOMB_INDEX, BOMB_RED_INDEX, MARKED_INDEX, NEGATIVE_1_INDEX,
+        NUMBER_9_INDEX, QUESTION_INDEX, TRIANGLE_DOWN_OFFSET, UNOPENED_INDEX,
     },
-    GameLoseEvent, GameResetEvent, GameWinEvent,
+    GameLoseEvent, GameWinEvent,
 };
 
-#[derive(Event)]
-pub struct TileUncoverEvent {
-    pub coord: (u32, u32),
-}
+const SAFE_COLOR: Color = Color::rgb(0.6, 1.0, 0.6);
+const MINE_COLOR: Color = Color::rgb(1.0, 0.6, 0.6);
+const GUESS_COLOR: Color = Color::rgb(1.0, 1.0, 0.5);
+const COUNT_COLOR: Color = Color::rgb(0.5, 0.0, 0.0);
 
 #[derive(Event)]
-pub struct TileNumberEvent {
-    pub coord: (u32, u32),
-    pub state: u32,
-}
-
-#[derive(Event)]
-pub struct TileCheckEvent {
+pub struct TileUncoverEvent {
     pub coord: (u32, u32),
-    pub state: u32,
 }
 
 #[derive(Event)]
@@ -30,179 +29,271 @@ pub struct TileMarkEvent {
     pub coord: (u32, u32),
 }
 
-pub fn re_uncover_tile_system(
-    last_step: Res<LastStep>,
-    mut tile_uncover_event: EventWriter<TileUncoverEvent>,
+pub fn safe_step_system(
+    mut tile_uncover_events: EventReader<TileUncoverEvent>,
+    mut last_step: ResMut<LastStep>,
 ) {
-    if last_step.uncover {
-        if let Some(coord) = last_step.coord {
-            tile_uncover_event.send(TileUncoverEvent { coord });
-        }
+    for uncover_event in tile_uncover_events.read() {
+        last_step.coord = Some(uncover_event.coord);
     }
 }
 
-pub fn safe_step_system(
+pub fn uncover_tiles_system(
     mut tile_uncover_events: EventReader<TileUncoverEvent>,
-    mut board_reset_event: EventWriter<GameResetEvent>,
-    board: Res<Board>,
-    board_options: Res<BoardOptions>,
-    mut last_step: ResMut<LastStep>,
+    mut game_win_event: EventWriter<GameWinEvent>,
+    mut game_lose_event: EventWriter<GameLoseEvent>,
+    mut board: ResMut<Board>,
+    mut message: ResMut<Message>,
 ) {
     for uncover_event in tile_uncover_events.read() {
-        let coord = uncover_event.coord;
-
-        if board.bomb_count_at(coord) == 0 && !board.is_bomb_at(coord) {
-            last_step.coord = Some(coord);
-            last_step.uncover = false;
-        } else {
-            if last_step.coord.is_none() {
-                last_step.uncover = true;
-            }
-
-            last_step.coord = Some(coord);
+        if board.status != GameStatus::Playing {
+            break;
+        }
+        let first = !board.started;
+        board.uncover(uncover_event.coord);
+        if first && board.no_guess_failed {
+            warn!("no guess-free layout found for seed {}", board.seed);
+            message.0 = "No guess-free layout found, this board may need guessing".to_string();
+        }
 
-            if last_step.uncover {
-                board_reset_event.send(GameResetEvent(board_options.clone()));
-            }
+        match board.status {
+            GameStatus::Won => game_win_event.send(GameWinEvent),
+            GameStatus::Lost => game_lose_event.send(GameLoseEvent),
+            GameStatus::Playing => (),
         }
     }
 }
 
-pub fn uncover_tiles_system(
+pub fn mark_tiles_system(
+    mut tile_mark_events: EventReader<TileMarkEvent>,
+    mut board: ResMut<Board>,
+) {
+    for mark_event in tile_mark_events.read() {
+        board.mark(mark_event.coord);
+    }
+}
+
+pub fn sync_tiles_system(
     tile_storage_query: Query<&TileStorage>,
-    mut tile_texture_inedx_query: Query<&mut TileTextureIndex>,
-    mut tile_uncover_events: EventReader<TileUncoverEvent>,
-    mut tile_number_event: EventWriter<TileNumberEvent>,
-    mut tile_check_event: EventWriter<TileCheckEvent>,
-    last_step: Res<LastStep>,
+    mut tile_texture_inedx_query: Query<(&TilePos, &mut TileTextureIndex)>,
     board: Res<Board>,
 ) {
-    if last_step.uncover {
-        return;
-    }
     if let Ok(tile_storage) = tile_storage_query.get_single() {
-        for uncover_event in tile_uncover_events.read() {
-            let coord = uncover_event.coord;
-            let tile_pos = TilePos::new(coord.0, coord.1);
-            if let Some(tile_entity) = tile_storage.checked_get(&tile_pos) {
-                if let Ok(mut texture_index) = tile_texture_inedx_query.get_mut(tile_entity) {
-                    let coord = (tile_pos.x, tile_pos.y);
-                    let mut state = board.get(coord);
-                    match texture_index.0 {
-                        UNOPENED_INDEX => {
-                            if state != BOMB_INDEX {
-                                state = board.bomb_count_at((tile_pos.x, tile_pos.y));
-                            }
-                            texture_index.0 = state;
-
-                            tile_check_event.send(TileCheckEvent { coord, state });
-                        }
-                        1..=8 => {
-                            tile_number_event.send(TileNumberEvent {
-                                coord,
-                                state: texture_index.0,
-                            });
-                        }
-                        _ => (),
-                    }
+        for tile_entity in tile_storage.iter().flatten() {
+            if let Ok((tile_pos, mut texture_index)) =
+                tile_texture_inedx_query.get_mut(*tile_entity)
+            {
+                let index = texture_index_at(&board, (tile_pos.x, tile_pos.y));
+                if texture_index.0 != index {
+                    texture_index.0 = index;
                 }
             }
         }
     }
 }
 
-pub fn number_tiles_system(
-    mut tile_number_events: EventReader<TileNumberEvent>,
-    mut tile_uncover_event: EventWriter<TileUncoverEvent>,
-    tile_storage_query: Query<&TileStorage>,
-    tile_texture_inedx_query: Query<&TileTextureIndex>,
+/// The atlas cell showing `count` around an opened tile, if it has one.
+pub fn number_index(count: i32) -> Option<u32> {
+    match count {
+        0..=8 => Some(count as u32),
+        9..=16 => Some(NUMBER_9_INDEX + count as u32 - 9),
+        -8..=-1 => Some(NEGATIVE_1_INDEX + count.unsigned_abs() - 1),
+        _ => None,
+    }
+}
+
+/// Writes the counts that have no atlas cell over their tiles.
+pub fn count_label_system(
+    mut commands: Commands,
+    label_query: Query<Entity, With<CountLabel>>,
+    tilemap_query: Query<(Entity, &TilemapGridSize, &TilemapType)>,
+    added_query: Query<(), Added<TilemapType>>,
     board: Res<Board>,
 ) {
-    if let Ok(tile_storage) = tile_storage_query.get_single() {
-        for number_event in tile_number_events.read() {
-            let (coord, state) = (number_event.coord, number_event.state);
-            let range = board.safe_square_at(coord);
-            let mut marked_count = 0;
-            let mut unopend_coords = vec![];
-            for coord in range {
-                let tile_pos = TilePos::new(coord.0, coord.1);
-                if let Some(tile_entity) = tile_storage.checked_get(&tile_pos) {
-                    if let Ok(texture_inedx) = tile_texture_inedx_query.get(tile_entity) {
-                        match texture_inedx.0 {
-                            MARKED_INDEX => {
-                                marked_count += 1;
-                            }
-                            UNOPENED_INDEX => {
-                                unopend_coords.push(coord);
-                            }
-                            _ => (),
-                        }
-                    }
-                }
+    if !board.is_changed() && added_query.is_empty() {
+        return;
+    }
+    for label in label_query.iter() {
+        commands.entity(label).despawn_recursive();
+    }
+
+    let counts = board
+        .coords()
+        .filter(|coord| board.tile(*coord) == Some(Tile::Opened) && !board.is_bomb_at(*coord))
+        .map(|coord| (coord, board.bomb_count_at(coord)))
+        .filter(|(_, count)| number_index(*count).is_none())
+        .collect::<Vec<_>>();
+    if counts.is_empty() {
+        return;
+    }
+
+    if let Ok((tilemap_entity, grid_size, map_type)) = tilemap_query.get_single() {
+        commands.entity(tilemap_entity).with_children(|children| {
+            for ((x, y), count) in counts {
+                let center = TilePos::new(x, y).center_in_world(grid_size, map_type);
+                children.spawn((
+                    Text2dBundle {
+                        text: Text::from_section(
+                            count.to_string(),
+                            TextStyle {
+                                font_size: 22.0,
+                                color: COUNT_COLOR,
+                                ..default()
+                            },
+                        ),
+                        transform: Transform::from_translation(center.extend(1.0))
+                            .with_scale(Vec3::splat(0.5)),
+                        ..default()
+                    },
+                    CountLabel,
+                ));
             }
+        });
+    }
+}
 
-            if state == marked_count {
-                for coord in unopend_coords {
-                    tile_uncover_event.send(TileUncoverEvent { coord });
-                }
+pub fn texture_index_at(board: &Board, coord: (u32, u32)) -> u32 {
+    let index = match board.tile(coord) {
+        Some(Tile::Opened) if board.exploded == Some(coord) && board.is_anti_mine_at(coord) => {
+            ANTI_MINE_RED_INDEX
+        }
+        Some(Tile::Opened) if board.exploded == Some(coord) => BOMB_RED_INDEX,
+        Some(Tile::Opened) if board.is_bomb_at(coord) => BOMB_INDEX,
+        // Counts without a cell of their own are drawn as a label on an empty tile.
+        Some(Tile::Opened) => number_index(board.bomb_count_at(coord)).unwrap_or(0),
+        // Stacked flags follow the single flag in the atlas.
+        Some(Tile::Marked) => MARKED_INDEX + board.flags_at(coord).max(1) - 1,
+        Some(Tile::AntiMarked) => ANTI_MARKED_INDEX,
+        Some(Tile::Questioned) if board.status == GameStatus::Lost && board.is_bomb_at(coord) => {
+            if board.is_anti_mine_at(coord) {
+                ANTI_MINE_INDEX
+            } else {
+                BOMB_INDEX
             }
         }
+        Some(Tile::Questioned) => QUESTION_INDEX,
+        Some(Tile::Unopened)
+            if board.status == GameStatus::Lost && board.is_anti_mine_at(coord) =>
+        {
+            ANTI_MINE_INDEX
+        }
+        Some(Tile::Unopened) if board.status == GameStatus::Lost && board.is_bomb_at(coord) => {
+            BOMB_INDEX
+        }
+        _ => UNOPENED_INDEX,
+    };
+    if board.options.shape == Shape::Triangle && !points_up(coord) {
+        index + TRIANGLE_DOWN_OFFSET
+    } else {
+        index
     }
 }
 
-pub fn check_tiles_system(
-    mut tile_check_events: EventReader<TileCheckEvent>,
-    mut tile_uncover_event: EventWriter<TileUncoverEvent>,
-    mut game_win_event: EventWriter<GameWinEvent>,
-    mut game_lose_event: EventWriter<GameLoseEvent>,
-    tile_texture_inedx_query: Query<&TileTextureIndex>,
-    board_options: Res<BoardOptions>,
-    board: Res<Board>,
+pub fn tile_color_system(
+    mut tile_color_query: Query<(&TilePos, &mut TileColor)>,
+    hint: Res<Hint>,
+    overlay: Res<ProbabilityOverlay>,
 ) {
-    for check_event in tile_check_events.read() {
-        let (coord, state) = (check_event.coord, check_event.state);
-        let mut check = || {
-            let uncover_count = tile_texture_inedx_query
-                .iter()
-                .filter(|index| index.0 == UNOPENED_INDEX || index.0 == MARKED_INDEX)
-                .count() as u32;
-            if uncover_count == board_options.bomb_count {
-                game_win_event.send(GameWinEvent);
-            }
-        };
+    if !hint.is_changed() && !overlay.is_changed() {
+        return;
+    }
 
-        match state {
-            OPENED_INDEX => {
-                for pos in board.safe_square_at(coord) {
-                    tile_uncover_event.send(TileUncoverEvent { coord: pos });
-                }
-                check();
-            }
-            BOMB_INDEX => {
-                game_lose_event.send(GameLoseEvent);
+    let chances = overlay.values.iter().copied().collect::<HashMap<_, _>>();
+    for (tile_pos, mut tile_color) in tile_color_query.iter_mut() {
+        let coord = (tile_pos.x, tile_pos.y);
+        let color = if hint.coord == Some(coord) {
+            match (hint.certain, hint.mine) {
+                (false, _) => GUESS_COLOR,
+                (true, true) => MINE_COLOR,
+                (true, false) => SAFE_COLOR,
             }
-            _ => {
-                check();
+        } else if let Some(chance) = chances.get(&coord) {
+            if *chance == 0.0 {
+                SAFE_COLOR
+            } else {
+                let chance = *chance as f32;
+                Color::rgb(1.0, 1.0 - 0.6 * chance, 1.0 - 0.6 * chance)
             }
+        } else {
+            Color::WHITE
+        };
+        if tile_color.0 != color {
+            tile_color.0 = color;
         }
     }
 }
 
-pub fn mark_tiles_system(
-    tile_storage_query: Query<&TileStorage>,
-    mut tile_texture_inedx_query: Query<&mut TileTextureIndex>,
-    mut tile_mark_events: EventReader<TileMarkEvent>,
-) {
-    if let Ok(tile_storage) = tile_storage_query.get_single() {
-        for mark_event in tile_mark_events.read() {
-            let coord = mark_event.coord;
-            let tile_pos = TilePos::new(coord.0, coord.1);
-            if let Some(tile_entity) = tile_storage.get(&tile_pos) {
-                if let Ok(mut texture_index) = tile_texture_inedx_query.get_mut(tile_entity) {
-                    if texture_index.0 == UNOPENED_INDEX {
-                        texture_index.0 = MARKED_INDEX;
-                    } else if texture_index.0 == MARKED_INDEX {
-                        texture_index.0 = UNOPENED_INDEX;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{BoardOptions, Neighbourhood, MAX_MINES_PER_CELL};
+
+    /// Atlas cells per shape; triangles keep the down-pointing ones after these.
+    const ATLAS_CELLS: u32 = 48;
+
+    fn is_number_cell(index: u32) -> bool {
+        index <= 8
+            || (NUMBER_9_INDEX..NUMBER_9_INDEX + 8).contains(&index)
+            || (NEGATIVE_1_INDEX..NEGATIVE_1_INDEX + 8).contains(&index)
+    }
+
+    #[test]
+    fn every_count_has_a_number_cell_or_a_label() {
+        let layouts = [
+            (Shape::Square, Neighbourhood::Moore),
+            (Shape::Square, Neighbourhood::Knight),
+            (Shape::Square, Neighbourhood::Cross),
+            (Shape::Square, Neighbourhood::Ring),
+            (Shape::Hex, Neighbourhood::Moore),
+            (Shape::Triangle, Neighbourhood::Moore),
+        ];
+        for (shape, neighbourhood) in layouts {
+            let options = BoardOptions {
+                shape,
+                neighbourhood,
+                multi_mine: true,
+                ..BoardOptions::new(7, 7, 0)
+            };
+            // (3, 3) points up on triangles and (2, 3) points down.
+            for coord in [(3, 3), (2, 3)] {
+                let mut board = Board::default();
+                board.reset(&options);
+                let around = board.safe_square_at(coord);
+                let most = (around.len() as u32 * MAX_MINES_PER_CELL) as i32;
+                let mut cells = Vec::new();
+
+                for count in -(around.len() as i32)..=most {
+                    for (i, (x, y)) in around.iter().enumerate() {
+                        let mines = if count < 0 {
+                            -(((i as i32) < -count) as i32)
+                        } else {
+                            (count - (i as u32 * MAX_MINES_PER_CELL) as i32)
+                                .clamp(0, MAX_MINES_PER_CELL as i32)
+                        };
+                        board.map[*x as usize][*y as usize] = mines;
+                    }
+                    board.tiles[coord.0 as usize][coord.1 as usize] = Tile::Opened;
+                    assert_eq!(board.bomb_count_at(coord), count);
+
+                    let mut index = texture_index_at(&board, coord);
+                    if shape == Shape::Triangle && !points_up(coord) {
+                        index -= TRIANGLE_DOWN_OFFSET;
+                    }
+                    assert!(index < ATLAS_CELLS, "{:?} {}: cell {}", shape, count, index);
+                    assert!(
+                        is_number_cell(index),
+                        "{:?} {}: cell {}",
+                        shape,
+                        count,
+                        index
+                    );
+                    match number_index(count) {
+                        Some(cell) => {
+                            assert_eq!(index, cell);
+                            assert!(!cells.contains(&cell));
+                            cells.push(cell);
+                        }
+                        None => assert_eq!(index, 0),
                     }
                 }
             }
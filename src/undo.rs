@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use crate::engine::{Board, GameStatus, Tile};
+
+/// The clock and the last uncovered tile, which step back along with the board.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Moment {
+    pub last_step: Option<(u32, u32)>,
+    pub elapsed: Duration,
+}
+
+/// One tile as it was on one side of a move.
+#[derive(Debug, Clone, PartialEq)]
+struct Cell {
+    coord: (u32, u32),
+    bombs: i32,
+    tile: Tile,
+    flags: u32,
+}
+
+/// The part of the board a move touched, as it was before or after the move.
+#[derive(Debug, Clone, PartialEq)]
+struct Side {
+    cells: Vec<Cell>,
+    status: GameStatus,
+    exploded: Option<(u32, u32)>,
+    seed: u64,
+    started: bool,
+    no_guess_failed: bool,
+    moment: Moment,
+}
+
+impl Side {
+    fn of(board: &Board, coords: &[(u32, u32)], moment: Moment) -> Self {
+        Side {
+            cells: coords
+                .iter()
+                .map(|coord| Cell {
+                    coord: *coord,
+                    bombs: board.get(*coord),
+                    tile: board.tile(*coord).unwrap_or_default(),
+                    flags: board.flags_at(*coord),
+                })
+                .collect(),
+            status: board.status,
+            exploded: board.exploded,
+            seed: board.seed,
+            started: board.started,
+            no_guess_failed: board.no_guess_failed,
+            moment,
+        }
+    }
+
+    fn apply(&self, board: &mut Board) -> Moment {
+        for cell in self.cells.iter() {
+            let (x, y) = (cell.coord.0 as usize, cell.coord.1 as usize);
+            board.map[x][y] = cell.bombs;
+            board.tiles[x][y] = cell.tile;
+            board.flags[x][y] = cell.flags;
+        }
+        board.status = self.status;
+        board.exploded = self.exploded;
+        board.seed = self.seed;
+        board.started = self.started;
+        board.no_guess_failed = self.no_guess_failed;
+        self.moment
+    }
+}
+
+struct Change {
+    before: Side,
+    after: Side,
+}
+
+/// Undo and redo for one game. Each move keeps only the tiles it changed.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+    /// The board before the moves being made, until `commit` compares it.
+    pending: Option<(Board, Moment)>,
+}
+
+impl History {
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.pending = None;
+    }
+
+    /// Remembers the board before a move.
+    pub fn begin(&mut self, board: &Board, moment: Moment) {
+        self.pending = Some((board.clone(), moment));
+    }
+
+    /// Records what the move since `begin` changed, if anything.
+    pub fn commit(&mut self, board: &Board, moment: Moment) {
+        let Some((before, before_moment)) = self.pending.take() else {
+            return;
+        };
+        if before == *board {
+            return;
+        }
+
+        let coords = board
+            .coords()
+            .filter(|coord| {
+                before.get(*coord) != board.get(*coord)
+                    || before.tile(*coord) != board.tile(*coord)
+                    || before.flags_at(*coord) != board.flags_at(*coord)
+            })
+            .collect::<Vec<_>>();
+        self.undo.push(Change {
+            before: Side::of(&before, &coords, before_moment),
+            after: Side::of(board, &coords, moment),
+        });
+        self.redo.clear();
+    }
+
+    /// Steps back one move, returning the moment to go back to. `now` is where
+    /// a redo comes back to.
+    pub fn undo(&mut self, board: &mut Board, now: Moment) -> Option<Moment> {
+        let mut change = self.undo.pop()?;
+        change.after.moment = now;
+        let moment = change.before.apply(board);
+        self.redo.push(change);
+        Some(moment)
+    }
+
+    pub fn redo(&mut self, board: &mut Board, now: Moment) -> Option<Moment> {
+        let mut change = self.redo.pop()?;
+        change.before.moment = now;
+        let moment = change.after.apply(board);
+        self.undo.push(change);
+        Some(moment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::parse_text;
+
+    fn play(history: &mut History, board: &mut Board, coord: (u32, u32), mark: bool) {
+        let moment = Moment {
+            last_step: Some(coord),
+            elapsed: Duration::from_secs(history.undo.len() as u64),
+        };
+        history.begin(board, moment);
+        if mark {
+            board.mark(coord);
+        } else {
+            board.uncover(coord);
+        }
+        history.commit(board, moment);
+    }
+
+    #[test]
+    fn undo_and_redo_across_the_end_of_a_game() {
+        let mut board = parse_text("*..\n...\n..*\n").unwrap();
+        let start = board.clone();
+        let mut history = History::default();
+
+        play(&mut history, &mut board, (0, 2), true);
+        // Opening a flagged tile changes nothing, so there is nothing to undo.
+        play(&mut history, &mut board, (0, 2), false);
+        play(&mut history, &mut board, (2, 0), false);
+        assert_eq!(board.status, GameStatus::Lost);
+        assert_eq!(history.undo.len(), 2);
+        let lost = board.clone();
+
+        let now = Moment::default();
+        let back = history.undo(&mut board, now).unwrap();
+        assert_eq!(back.last_step, Some((2, 0)));
+        assert_eq!(board.status, GameStatus::Playing);
+        assert_eq!(board.exploded, None);
+        assert_eq!(board.tile((2, 0)), Some(Tile::Unopened));
+        assert_eq!(history.redo(&mut board, now), Some(now));
+        assert_eq!(board, lost);
+
+        history.undo(&mut board, now);
+        history.undo(&mut board, now);
+        assert_eq!(board, start);
+        assert!(history.undo.is_empty());
+
+        // A new move drops the moves that could have been redone.
+        play(&mut history, &mut board, (1, 1), false);
+        assert!(history.redo.is_empty());
+        for coord in [(1, 0), (0, 0), (0, 1), (2, 1), (1, 2), (2, 2)] {
+            play(&mut history, &mut board, coord, false);
+        }
+        assert_eq!(board.status, GameStatus::Won);
+        let won = board.clone();
+        history.undo(&mut board, now);
+        assert_eq!(board.status, GameStatus::Playing);
+        history.redo(&mut board, now);
+        assert_eq!(board, won);
+    }
+
+    #[test]
+    fn first_click_can_be_undone() {
+        let mut board = Board::default();
+        board.reset(&crate::engine::BoardOptions::new(9, 9, 10).with_seed(Some(1)));
+        let start = board.clone();
+        let mut history = History::default();
+
+        play(&mut history, &mut board, (4, 4), false);
+        assert!(board.started);
+        // Only the tiles the move touched are kept.
+        assert!(history.undo[0].before.cells.len() < 81);
+        history.undo(&mut board, Moment::default());
+        assert_eq!(board, start);
+    }
+
+    #[test]
+    fn replay_from_the_layout_before_the_first_click() {
+        let options = crate::engine::BoardOptions::new(9, 9, 30);
+        for seed in 0..20 {
+            let options = options.clone().with_seed(Some(seed));
+            let play_game = |board: &mut Board| {
+                let mut history = History::default();
+                play(&mut history, board, (0, 0), false);
+                // The first click is undone, so the second one moves mines again.
+                history.undo(board, Moment::default());
+                play(&mut history, board, (8, 8), false);
+            };
+
+            let mut board = Board::default();
+            board.reset(&options);
+            let layout = board.map.clone();
+            play_game(&mut board);
+            assert_ne!(board.status, GameStatus::Lost);
+
+            let mut replay = Board::default();
+            replay.reset(&options);
+            replay.load_layout(layout);
+            play_game(&mut replay);
+            assert_eq!(replay, board);
+        }
+    }
+}